@@ -0,0 +1,254 @@
+use super::{format::Format, paint, Command};
+use crate::{
+    dirs::Dirs,
+    path::resolve,
+    repository::{config::File, template::UndefinedVariables},
+    Repository,
+};
+use anyhow::Result;
+use clap::{arg, command, ArgMatches};
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+use std::{fs, path::Path};
+use termion::{color, style};
+
+#[derive(Debug)]
+pub struct Diff;
+
+/// How much one file changed, what `--stat` shows
+#[derive(Serialize)]
+struct FileStat {
+    path: String,
+    insertions: usize,
+    deletions: usize,
+    binary: bool,
+}
+
+/// `--stat` as printed with `--format`
+#[derive(Serialize)]
+struct Stat {
+    files: Vec<FileStat>,
+    changed: usize,
+    insertions: usize,
+    deletions: usize,
+}
+
+impl Command for Diff {
+    fn run(matches: &ArgMatches) -> Result<()> {
+        let name = matches.get_one::<String>("NAME").unwrap();
+        let format = Format::from(matches);
+        // there's no structured form of a unified diff, only of the summary
+        let stat = *matches.get_one::<bool>("stat").unwrap() || format.is_some();
+
+        let filters: Vec<_> = match matches.get_many::<String>("FILES") {
+            Some(files) => files.map(|f| resolve(f)).collect::<Result<_, _>>()?,
+            None => vec![],
+        };
+
+        let path = Dirs::Data.join(name);
+        let repository = Repository::open(&path)?;
+
+        let files: Vec<_> = repository
+            .config
+            .host_files()
+            .into_iter()
+            .filter(|file| {
+                filters.is_empty() || filters.iter().any(|f| file.stored_path().starts_with(f))
+            })
+            .collect();
+
+        let profiles = repository.config.host_profiles();
+        let mut stats = vec![];
+
+        for file in files {
+            let stored = Dirs::Files(&repository.config).join(file.to_string());
+            let live = file.stored_path();
+
+            // templates are compared the way they would be rendered on this machine
+            let old = match stored.exists() {
+                true => match repository.config.deployed_contents(file, &profiles) {
+                    Ok(contents) => contents,
+                    Err(err) if err.is::<UndefinedVariables>() => {
+                        eprintln!("Can't compare '{}': {err}", file.user_path());
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                },
+                false => vec![],
+            };
+            let new = read(&live)?;
+
+            if old == new {
+                continue;
+            }
+
+            let binary = text(&old).is_none() || text(&new).is_none();
+
+            let (insertions, deletions) = match (text(&old), text(&new)) {
+                (Some(old), Some(new)) => {
+                    let diff = TextDiff::from_lines(old, new);
+
+                    let count = |tag| diff.iter_all_changes().filter(|c| c.tag() == tag).count();
+
+                    if !stat {
+                        print_unified(file, &diff, old.is_empty(), new.is_empty());
+                    }
+
+                    (count(ChangeTag::Insert), count(ChangeTag::Delete))
+                }
+                _ => {
+                    if !stat {
+                        println!("Binary files differ for '{}'", file.user_path());
+                    }
+
+                    (0, 0)
+                }
+            };
+
+            stats.push(FileStat {
+                path: file.user_path(),
+                insertions,
+                deletions,
+                binary,
+            });
+        }
+
+        let total = Stat {
+            changed: stats.len(),
+            insertions: stats.iter().map(|s| s.insertions).sum(),
+            deletions: stats.iter().map(|s| s.deletions).sum(),
+            files: stats,
+        };
+
+        if let Some(format) = format {
+            return format.print(&total);
+        }
+
+        if stat {
+            for file in &total.files {
+                print_stat(file);
+            }
+
+            let Stat {
+                changed,
+                insertions,
+                deletions,
+                ..
+            } = total;
+
+            println!(
+                " {changed} file{} changed, {insertions} insertion{}(+), {deletions} deletion{}(-)",
+                plural(changed),
+                plural(insertions),
+                plural(deletions),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Missing files are treated as empty so additions and removals show up as whole-file diffs
+fn read(path: &Path) -> Result<Vec<u8>> {
+    match path.exists() {
+        true => Ok(fs::read(path)?),
+        false => Ok(vec![]),
+    }
+}
+
+/// Returns `None` for contents that look binary, like git does
+fn text(contents: &[u8]) -> Option<&str> {
+    let head = &contents[..contents.len().min(8000)];
+
+    if head.contains(&0) {
+        return None;
+    }
+
+    std::str::from_utf8(contents).ok()
+}
+
+fn print_unified<'a>(
+    file: &File,
+    diff: &TextDiff<'a, 'a, '_, str>,
+    old_missing: bool,
+    new_missing: bool,
+) {
+    let old = match old_missing {
+        true => "/dev/null".into(),
+        false => format!("export/{}", file.user_path()),
+    };
+    let new = match new_missing {
+        true => "/dev/null".into(),
+        false => file.user_path(),
+    };
+
+    println!("{}--- {old}", paint(style::Bold));
+    println!("+++ {new}{}", paint(style::Reset));
+
+    for hunk in diff.unified_diff().iter_hunks() {
+        println!(
+            "{}{}{}",
+            paint(color::Fg(color::Cyan)),
+            hunk.header(),
+            paint(style::Reset)
+        );
+
+        for change in hunk.iter_changes() {
+            let (sign, fg) = match change.tag() {
+                ChangeTag::Delete => ("-", paint(color::Fg(color::Red))),
+                ChangeTag::Insert => ("+", paint(color::Fg(color::Green))),
+                ChangeTag::Equal => (" ", paint(color::Fg(color::Reset))),
+            };
+
+            print!("{fg}{sign}{change}{}", paint(style::Reset));
+
+            if change.missing_newline() {
+                println!("\n\\ No newline at end of file");
+            }
+        }
+    }
+}
+
+fn print_stat(stat: &FileStat) {
+    let &FileStat {
+        ref path,
+        insertions,
+        deletions,
+        binary,
+    } = stat;
+
+    if binary {
+        println!(" {path} | Bin");
+        return;
+    }
+
+    println!(
+        " {path} | {} {}{}{}{}{}",
+        insertions + deletions,
+        paint(color::Fg(color::Green)),
+        "+".repeat(insertions.min(40)),
+        paint(color::Fg(color::Red)),
+        "-".repeat(deletions.min(40)),
+        paint(style::Reset),
+    );
+}
+
+fn plural(count: usize) -> &'static str {
+    match count {
+        1 => "",
+        _ => "s",
+    }
+}
+
+#[allow(clippy::from_over_into)]
+impl Into<clap::Command> for Diff {
+    fn into(self) -> clap::Command {
+        command!("diff")
+            .about("Show line differences between an export and your system")
+            .args([
+                arg!(<NAME> "Export name"),
+                arg!([FILES] ... "Only show differences for these files"),
+                arg!(--stat "Only show a summary of the changes"),
+            ])
+    }
+}
@@ -1,13 +1,81 @@
-use super::Command;
-use crate::{dirs::Dirs, repository::config::File, Expand, Repository};
+use super::{format::Format, picker::Picker, Command};
+use crate::{
+    dirs::Dirs,
+    path::resolve,
+    repository::{
+        config::{Deploy, File},
+        history::Revision,
+        leak_report, Plan,
+    },
+    Config, Expand, Repository,
+};
 use anyhow::Result;
 use clap::{arg, command, ArgMatches};
 use dialoguer::{Confirm, MultiSelect};
+use serde::Serialize;
 use std::{fs, path::PathBuf};
 
 #[derive(Debug)]
 pub struct Export;
 
+/// What saving printed with `--format`
+#[derive(Serialize)]
+struct Saved<'a> {
+    dry_run: bool,
+    actions: &'a Plan,
+    /// Lines a dry run found that would make the save refuse
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    secrets: Vec<Suspect>,
+}
+
+#[derive(Serialize)]
+struct Suspect {
+    path: String,
+    line: usize,
+    kind: &'static str,
+}
+
+#[derive(Serialize)]
+struct Remote {
+    name: String,
+    url: String,
+}
+
+#[derive(Serialize)]
+struct Pushed<'a> {
+    export: &'a str,
+    remote: &'a str,
+}
+
+#[derive(Serialize)]
+struct Bundled<'a> {
+    export: &'a str,
+    output: String,
+}
+
+#[derive(Serialize)]
+struct ProfileSummary<'a> {
+    name: &'a str,
+    /// Whether this host uses it
+    active: bool,
+    files: usize,
+    hosts: &'a [String],
+}
+
+#[derive(Serialize)]
+struct CheckedOut<'a> {
+    revision: &'a Revision,
+    /// What `--import` put in place
+    #[serde(skip_serializing_if = "Option::is_none")]
+    actions: Option<&'a Plan>,
+}
+
+#[derive(Serialize)]
+struct Deleted<'a> {
+    export: &'a str,
+    path: String,
+}
+
 impl Command for Export {
     fn run(matches: &ArgMatches) -> Result<()> {
         let name = matches.get_one::<String>("NAME").unwrap();
@@ -16,38 +84,96 @@ impl Command for Export {
             Some(("add", submatches)) => Self::add(name, matches, submatches),
             Some(("remove", submatches)) => Self::remove(name, matches, submatches),
             Some(("delete", submatches)) => Self::delete(name, matches, submatches),
-            Some(("create", _)) => Self::create(name),
+            Some(("save", submatches)) => Self::save(name, submatches),
+            Some(("remote", submatches)) => Self::remote(name, submatches),
+            Some(("push", submatches)) => Self::push(name, submatches),
+            Some(("bundle", submatches)) => Self::bundle(name, submatches),
+            Some(("log", submatches)) => Self::log(name, submatches),
+            Some(("ignore", submatches)) => Self::ignore(name, submatches),
+            Some(("profile", submatches)) => Self::profile(name, submatches),
+            Some(("checkout", submatches)) => Self::checkout(name, submatches),
+            Some(("create", submatches)) => Self::create(name, submatches),
             _ => Ok(()),
         }
     }
 }
 
 impl Export {
-    fn create(name: &str) -> Result<()> {
+    fn create(name: &str, submatches: &ArgMatches) -> Result<()> {
         let dest = Dirs::Data;
 
-        Repository::init(name, &dest)?;
+        let mut repository = Repository::init(name, &dest)?;
 
-        Ok(())
+        if *submatches.get_one::<bool>("link").unwrap() {
+            repository.config.deploy = Deploy::Link;
+        }
+
+        if *submatches.get_one::<bool>("mtimes").unwrap() {
+            repository.config.mtimes = true;
+        }
+
+        let format = Format::from(submatches);
+        let plan = repository.config.save(None, false, format.is_some())?;
+
+        match format {
+            Some(format) => format.print(&Saved {
+                dry_run: false,
+                actions: &plan,
+                secrets: vec![],
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn save(name: &str, submatches: &ArgMatches) -> Result<()> {
+        let path = Dirs::Data.join(name);
+        let mut repository = Repository::open(&path)?;
+
+        Self::save_config(&mut repository.config, submatches)
     }
 
     fn add(name: &str, _matches: &ArgMatches, submatches: &ArgMatches) -> Result<()> {
         let path = Dirs::Data.join(name);
         let mut repository = Repository::open(&path)?;
 
-        let mut files: Vec<_> = submatches
-            .get_many::<String>("FILES")
-            .unwrap()
-            .map(PathBuf::from)
-            .filter_map(|p| p.expand().ok())
-            .flatten()
-            .map(File::from)
-            .collect();
+        let paths: Vec<PathBuf> = match *submatches.get_one::<bool>("interactive").unwrap() {
+            true => Picker::new(&repository.config)?.run()?,
+            false => submatches
+                .get_many::<String>("FILES")
+                .unwrap()
+                .filter_map(|p| resolve(p).ok())
+                .collect(),
+        };
+
+        if paths.is_empty() {
+            println!("There are no files to add");
+            return Ok(());
+        }
 
-        repository.config.append(&mut files);
-        repository.config.save()?;
+        let profile = submatches.get_one::<String>("profile").map(String::as_str);
+        let files = repository.config.add_paths(&paths, profile)?;
 
-        Ok(())
+        if *submatches.get_one::<bool>("template").unwrap() {
+            repository.config.mark_templates(&files);
+        }
+
+        if *submatches.get_one::<bool>("secret").unwrap() {
+            repository.config.mark_secrets(&files);
+
+            let exposed = repository.config.committed_in_plaintext(&files)?;
+
+            if !exposed.is_empty() {
+                let paths: Vec<_> = exposed.iter().map(File::user_path).collect();
+
+                eprintln!(
+                    "Warning: these files stay unencrypted in the export's history and get pushed with it: {}\n\
+                     Change the secrets they hold, or recreate the export without them",
+                    paths.join(", ")
+                );
+            }
+        }
+
+        Self::save_config(&mut repository.config, submatches)
     }
 
     fn remove(name: &str, _matches: &ArgMatches, submatches: &ArgMatches) -> Result<()> {
@@ -55,12 +181,13 @@ impl Export {
         let mut repository = Repository::open(&path)?;
 
         let interactive = *submatches.get_one::<bool>("interactive").unwrap();
+        let profile = submatches.get_one::<String>("profile").map(String::as_str);
 
         let mut files: Vec<File> = match interactive {
             true => {
                 let options: Vec<String> = repository
                     .config
-                    .files
+                    .files_mut(profile)
                     .iter()
                     .map(File::user_path)
                     .collect();
@@ -78,22 +205,298 @@ impl Export {
                     .filter_map(|s| File::try_from(s).ok())
                     .collect()
             }
-            false => submatches
-                .get_many::<String>("FILES")
-                .unwrap()
-                .map(PathBuf::from)
-                .filter_map(|path| path.expand().ok())
-                .flatten()
-                .map(File::from)
-                .collect(),
+            false => {
+                // the directory itself may already be gone locally
+                let directories: Vec<_> = submatches
+                    .get_many::<String>("FILES")
+                    .unwrap()
+                    .filter_map(|p| resolve(p).ok())
+                    .map(File::from)
+                    .collect();
+
+                repository.config.remove_directories(&directories, profile);
+
+                submatches
+                    .get_many::<String>("FILES")
+                    .unwrap()
+                    .map(PathBuf::from)
+                    .filter_map(|path| path.expand().ok())
+                    .flatten()
+                    .map(File::from)
+                    .collect()
+            }
+        };
+
+        repository.config.remove(&mut files, profile);
+
+        Self::save_config(&mut repository.config, submatches)
+    }
+
+    fn save_config(config: &mut Config, submatches: &ArgMatches) -> Result<()> {
+        let message = submatches.get_one::<String>("message").map(String::as_str);
+        let allow_secrets = *submatches.get_one::<bool>("allow-secrets").unwrap();
+        let dry_run = *submatches.get_one::<bool>("dry-run").unwrap();
+        let format = Format::from(submatches);
+
+        if !dry_run {
+            let plan = config.save(message, allow_secrets, format.is_some())?;
+
+            return match format {
+                Some(format) => format.print(&Saved {
+                    dry_run,
+                    actions: &plan,
+                    secrets: vec![],
+                }),
+                None => Ok(()),
+            };
+        }
+
+        config.refresh()?;
+
+        let plan = config.plan_save(message)?;
+        let leaks = match allow_secrets {
+            true => vec![],
+            false => plan.leaks(config),
         };
 
-        repository.config.remove(&mut files);
-        repository.config.save()?;
+        if let Some(format) = format {
+            let secrets = leaks
+                .iter()
+                .map(|(file, leak)| Suspect {
+                    path: file.user_path(),
+                    line: leak.line,
+                    kind: leak.kind,
+                })
+                .collect();
+
+            return format.print(&Saved {
+                dry_run,
+                actions: &plan,
+                secrets,
+            });
+        }
+
+        println!("{plan}");
+
+        if !leaks.is_empty() {
+            println!("{}", leak_report(&leaks));
+        }
+
+        Ok(())
+    }
+
+    fn remote(name: &str, submatches: &ArgMatches) -> Result<()> {
+        let path = Dirs::Data.join(name);
+        let repository = Repository::open(&path)?;
+
+        match submatches.subcommand() {
+            Some(("add", matches)) => {
+                let remote = matches.get_one::<String>("REMOTE").unwrap();
+                let url = matches.get_one::<String>("URL").unwrap();
+
+                repository.add_remote(remote, url)?;
+            }
+            Some(("remove", matches)) => {
+                let remote = matches.get_one::<String>("REMOTE").unwrap();
+
+                repository.remove_remote(remote)?;
+            }
+            _ => {
+                let remotes: Vec<_> = repository
+                    .remotes()?
+                    .into_iter()
+                    .map(|(name, url)| Remote { name, url })
+                    .collect();
+
+                if let Some(format) = Format::from(submatches) {
+                    return format.print(&remotes);
+                }
+
+                for remote in remotes {
+                    println!("{}\t{}", remote.name, remote.url);
+                }
+            }
+        }
 
         Ok(())
     }
 
+    fn push(name: &str, submatches: &ArgMatches) -> Result<()> {
+        let path = Dirs::Data.join(name);
+        let repository = Repository::open(&path)?;
+
+        let remote = submatches.get_one::<String>("REMOTE").unwrap();
+
+        repository.push(remote)?;
+
+        match Format::from(submatches) {
+            Some(format) => format.print(&Pushed {
+                export: name,
+                remote,
+            }),
+            None => {
+                println!("Pushed '{name}' to '{remote}'");
+                Ok(())
+            }
+        }
+    }
+
+    fn bundle(name: &str, submatches: &ArgMatches) -> Result<()> {
+        let path = Dirs::Data.join(name);
+        let repository = Repository::open(&path)?;
+
+        let output = match submatches.get_one::<String>("OUTPUT") {
+            Some(output) => PathBuf::from(output),
+            None => PathBuf::from(format!("{name}.tar.gz")),
+        };
+
+        repository.bundle(&output, *submatches.get_one::<bool>("history").unwrap())?;
+
+        match Format::from(submatches) {
+            Some(format) => format.print(&Bundled {
+                export: name,
+                output: output.display().to_string(),
+            }),
+            None => {
+                println!("Bundled '{name}' into '{}'", output.display());
+                Ok(())
+            }
+        }
+    }
+
+    fn profile(name: &str, submatches: &ArgMatches) -> Result<()> {
+        let path = Dirs::Data.join(name);
+        let mut repository = Repository::open(&path)?;
+
+        let Some(profile) = submatches.get_one::<String>("PROFILE") else {
+            let active = repository.config.host_profiles();
+
+            let profiles: Vec<_> = repository
+                .config
+                .profiles
+                .iter()
+                .map(|(name, profile)| ProfileSummary {
+                    name,
+                    active: active.contains(name),
+                    files: profile.files.len(),
+                    hosts: &profile.hosts,
+                })
+                .collect();
+
+            if let Some(format) = Format::from(submatches) {
+                return format.print(&profiles);
+            }
+
+            for profile in profiles {
+                let marker = if profile.active { "*" } else { " " };
+
+                println!(
+                    "{marker} {} ({} files) hosts: {}",
+                    profile.name,
+                    profile.files,
+                    profile.hosts.join(", ")
+                );
+            }
+
+            return Ok(());
+        };
+
+        let hosts: Vec<String> = match submatches.get_many::<String>("host") {
+            Some(hosts) => hosts.cloned().collect(),
+            None => vec![],
+        };
+
+        repository
+            .config
+            .profiles
+            .entry(profile.into())
+            .or_default()
+            .hosts = hosts;
+
+        Self::save_config(&mut repository.config, submatches)
+    }
+
+    fn ignore(name: &str, submatches: &ArgMatches) -> Result<()> {
+        let path = Dirs::Data.join(name);
+        let mut repository = Repository::open(&path)?;
+
+        let Some(patterns) = submatches.get_many::<String>("PATTERNS") else {
+            if let Some(format) = Format::from(submatches) {
+                return format.print(&repository.config.ignore);
+            }
+
+            for pattern in &repository.config.ignore {
+                println!("{pattern}");
+            }
+
+            return Ok(());
+        };
+
+        for pattern in patterns {
+            if !repository.config.ignore.contains(pattern) {
+                repository.config.ignore.push(pattern.clone());
+            }
+        }
+
+        Self::save_config(&mut repository.config, submatches)
+    }
+
+    fn log(name: &str, submatches: &ArgMatches) -> Result<()> {
+        let path = Dirs::Data.join(name);
+        let repository = Repository::open(&path)?;
+
+        let log = repository.log()?;
+
+        if let Some(format) = Format::from(submatches) {
+            return format.print(&log);
+        }
+
+        if log.is_empty() {
+            println!("'{name}' has no commits yet");
+        }
+
+        for revision in log {
+            println!("{revision}");
+        }
+
+        Ok(())
+    }
+
+    fn checkout(name: &str, submatches: &ArgMatches) -> Result<()> {
+        let path = Dirs::Data.join(name);
+        let mut repository = Repository::open(&path)?;
+
+        let rev = submatches.get_one::<String>("REV").unwrap();
+
+        let format = Format::from(submatches);
+        let revision = repository.checkout(rev)?;
+
+        if format.is_none() {
+            println!("{revision}");
+        }
+
+        let plan = match *submatches.get_one::<bool>("import").unwrap() {
+            true => {
+                let config = &repository.config;
+                let plan = config.plan_import(&config.host_profiles(), config.deploy, false)?;
+
+                plan.apply(config, format.is_some())?;
+                config.mark_imported()?;
+
+                Some(plan)
+            }
+            false => None,
+        };
+
+        match format {
+            Some(format) => format.print(&CheckedOut {
+                revision: &revision,
+                actions: plan.as_ref(),
+            }),
+            None => Ok(()),
+        }
+    }
+
     fn delete(name: &str, _matches: &ArgMatches, submatches: &ArgMatches) -> Result<()> {
         let path = Dirs::Data.join(name);
 
@@ -108,9 +511,17 @@ impl Export {
         }
 
         fs::remove_dir_all(&path)?;
-        println!("Deleted '{}'", path.display());
 
-        Ok(())
+        match Format::from(submatches) {
+            Some(format) => format.print(&Deleted {
+                export: name,
+                path: path.display().to_string(),
+            }),
+            None => {
+                println!("Deleted '{}'", path.display());
+                Ok(())
+            }
+        }
     }
 }
 
@@ -119,19 +530,78 @@ impl Into<clap::Command> for Export {
     fn into(self) -> clap::Command {
         command!("export")
             .about("Save your dotfiles")
-            .arg(arg!(<NAME> "Export name"))
+            .args([
+                arg!(<NAME> "Export name"),
+                arg!(-n --"dry-run" "Show what would be done without doing it").global(true),
+                arg!(-m --message <MESSAGE> "Commit message for the saved changes").global(true),
+                arg!(--"allow-secrets" "Commit files even if they look like they hold secrets")
+                    .global(true),
+            ])
             .subcommands([
                 command!("add")
                     .about("Add file(s)")
-                    .arg(arg!(<FILES> ... "Files you want to add")),
+                    .arg_required_else_help(true)
+                    .args([
+                        arg!([FILES] ... "Files you want to add")
+                            .required_unless_present("interactive"),
+                        arg!(-i --interactive "Browse for the files you want to add"),
+                        arg!(-p --profile <PROFILE> "Only deploy them on machines using this profile"),
+                        arg!(-t --template "Render them with the export variables when importing"),
+                        arg!(-s --secret "Store them encrypted inside the export"),
+                    ]),
                 command!("remove")
                     .about("Remove file(s)")
                     .arg_required_else_help(true)
                     .args([
-                        arg!([FILES] ... "Files you want to remove"),
+                        arg!([FILES] ... "Files you want to remove")
+                            .required_unless_present("interactive"),
                         arg!(-i --interactive "Choose the files you want to remove on the fly"),
+                        arg!(-p --profile <PROFILE> "Profile the files belong to"),
+                    ]),
+                command!("save").about("Sync changed files into the export"),
+                command!("remote")
+                    .about("Manage the remotes the export is pushed to")
+                    .subcommands([
+                        command!("add")
+                            .about("Add a remote")
+                            .args([arg!(<REMOTE> "Remote name"), arg!(<URL> "Remote url")]),
+                        command!("remove")
+                            .about("Remove a remote")
+                            .arg(arg!(<REMOTE> "Remote name")),
+                        command!("list").about("List remotes"),
+                    ]),
+                command!("push")
+                    .about("Push the export to a remote")
+                    .arg(arg!([REMOTE] "Remote name").default_value("origin")),
+                command!("bundle")
+                    .about("Pack the export into a .tar.gz that import accepts")
+                    .args([
+                        arg!([OUTPUT] "Where to write it, <NAME>.tar.gz by default"),
+                        arg!(--history "Include the git history"),
+                    ]),
+                command!("profile")
+                    .about("List profiles, or set which hosts use one")
+                    .args([
+                        arg!([PROFILE] "Profile name"),
+                        arg!(-H --host <HOST> ... "Hostname using this profile, `*` works as a wildcard")
+                            .requires("PROFILE"),
+                    ]),
+                command!("ignore")
+                    .about("List or add patterns left out when adding directories")
+                    .arg(arg!([PATTERNS] ... "Gitignore-style patterns")),
+                command!("log").about("Show the history of the export"),
+                command!("checkout")
+                    .about("Bring the export back to a previous revision")
+                    .args([
+                        arg!(<REV> "Revision to go back to"),
+                        arg!(-i --import "Also put the files of that revision in place"),
+                    ]),
+                command!("create")
+                    .about("Create a new export")
+                    .args([
+                        arg!(-l --link "Symlink files by default when importing"),
+                        arg!(--mtimes "Also keep modification times of the files"),
                     ]),
-                command!("create").about("Create a new export"),
                 command!("delete")
                     .about("Deletes a export")
                     .arg(arg!(-y --yes "Don't ask confirmation")),
@@ -0,0 +1,35 @@
+use anyhow::Result;
+use clap::ArgMatches;
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// Machine-readable output picked with the global `--format`,
+/// commands print plain text without it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+}
+
+impl Format {
+    pub fn from(matches: &ArgMatches) -> Option<Self> {
+        match matches.get_one::<String>("format")?.as_str() {
+            "json" => Some(Self::Json),
+            "yaml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Writes `value` to stdout, failing rather than panicking when whatever
+    /// reads it went away
+    pub fn print<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        let text = match self {
+            Self::Json => serde_json::to_string_pretty(value)? + "\n",
+            Self::Yaml => serde_yaml::to_string(value)?,
+        };
+
+        io::stdout().lock().write_all(text.as_bytes())?;
+
+        Ok(())
+    }
+}
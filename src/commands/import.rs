@@ -1,49 +1,135 @@
-use super::Command;
-use crate::{dirs::Dirs, Repository};
+use super::{format::Format, Command};
+use crate::{
+    dirs::Dirs,
+    repository::{config::Deploy, Plan},
+    Repository,
+};
 use anyhow::Result;
 use clap::{arg, command, ArgMatches};
-use std::fs;
+use serde::Serialize;
 
 #[derive(Debug)]
 pub struct Import;
 
+/// What the user asked `import` to do besides the url
+#[derive(Debug)]
+struct Options {
+    deploy: Option<Deploy>,
+    profiles: Option<Vec<String>>,
+    overwrite: bool,
+    quiet: bool,
+    dry_run: bool,
+    format: Option<Format>,
+}
+
+/// What `import` printed with `--format`
+#[derive(Serialize)]
+struct Imported<'a> {
+    export: &'a str,
+    profiles: &'a [String],
+    dry_run: bool,
+    /// Files written through sudo or doas
+    needs_root: Vec<String>,
+    actions: &'a Plan,
+}
+
 impl Command for Import {
     fn run(matches: &ArgMatches) -> Result<()> {
         let url = matches.get_one::<String>("URL").unwrap();
 
-        let overwrite = *matches.get_one::<bool>("overwrite").unwrap();
-        let quiet = *matches.get_one::<bool>("quiet").unwrap();
+        let deploy = match (
+            *matches.get_one::<bool>("link").unwrap(),
+            *matches.get_one::<bool>("copy").unwrap(),
+        ) {
+            (true, _) => Some(Deploy::Link),
+            (_, true) => Some(Deploy::Copy),
+            _ => None,
+        };
 
-        match matches.subcommand() {
-            _ => Self::import(url, overwrite, quiet),
-        }
+        let options = Options {
+            deploy,
+            profiles: matches
+                .get_many::<String>("profile")
+                .map(|profiles| profiles.cloned().collect()),
+            overwrite: *matches.get_one::<bool>("overwrite").unwrap(),
+            quiet: *matches.get_one::<bool>("quiet").unwrap(),
+            dry_run: *matches.get_one::<bool>("dry-run").unwrap(),
+            format: Format::from(matches),
+        };
+
+        Self::import(url, options)
     }
 }
 
 impl Import {
-    fn import(url: &str, overwrite: bool, quiet: bool) -> Result<()> {
-        let dest = Dirs::Data.path();
+    fn import(url: &str, options: Options) -> Result<()> {
+        let dry_run = options.dry_run;
+
+        // a dry run clones somewhere disposable so no existing export is touched
+        let dest = match dry_run {
+            true => Dirs::Cache.path(),
+            false => Dirs::Data.path(),
+        };
 
         let repository = Repository::clone(url, dest)?;
+        let result = Self::deploy(&repository, options);
 
-        for file in &repository.config.files {
-            let name = file.name();
-            let from = Dirs::Files(&repository.config).join(file.to_string());
-            let to = file.stored_path();
+        // the disposable clone goes away whether or not planning worked
+        if dry_run {
+            repository.delete()?;
+        }
 
-            if !overwrite && to.exists() {
-                let old = &to.parent().unwrap().join(format!("{name}.bcup"));
+        result
+    }
 
-                match fs::copy(&to, old) {
-                    Ok(_) => if !quiet { println!("Backed up existent '{name}'") },
-                    Err(err) => return Err(err.into()),
-                };
-            }
+    fn deploy(repository: &Repository, options: Options) -> Result<()> {
+        let Options {
+            deploy,
+            profiles,
+            overwrite,
+            quiet,
+            dry_run,
+            format,
+        } = options;
 
-            match fs::copy(&from, &to) {
-                Ok(_) => if !quiet { println!("Imported '{name}'") },
-                Err(err) => return Err(err.into()),
-            };
+        let quiet = quiet || format.is_some();
+        let deploy = deploy.unwrap_or(repository.config.deploy);
+
+        let profiles = profiles.unwrap_or_else(|| repository.config.host_profiles());
+
+        if !quiet && !profiles.is_empty() {
+            println!("Using profiles: {}", profiles.join(", "));
+        }
+
+        let plan = repository
+            .config
+            .plan_import(&profiles, deploy, overwrite)?;
+
+        let root: Vec<_> = plan
+            .root_files()
+            .iter()
+            .map(|file| file.user_path())
+            .collect();
+
+        if !dry_run {
+            plan.apply(&repository.config, quiet)?;
+            repository.config.mark_imported()?;
+        }
+
+        if let Some(format) = format {
+            format.print(&Imported {
+                export: &repository.config.name,
+                profiles: &profiles,
+                dry_run,
+                needs_root: root,
+                actions: &plan,
+            })?;
+        } else if dry_run {
+            println!("{plan}");
+
+            if !root.is_empty() {
+                println!("Needs root: {}", root.join(", "));
+            }
         }
 
         Ok(())
@@ -57,9 +143,14 @@ impl Into<clap::Command> for Import {
             .about("Import dotfiles")
             .arg_required_else_help(true)
             .args([
-                arg!(<URL> "Repo url"),
+                arg!(<URL> "Repo url, local repository or .tar.gz bundle"),
                 arg!(-o --overwrite "Ignores if some file already exists"),
                 arg!(-q --quiet "Do not output any information"),
+                arg!(-l --link "Symlink files into the export instead of copying them")
+                    .conflicts_with("copy"),
+                arg!(-c --copy "Copy files even if the export prefers links"),
+                arg!(-n --"dry-run" "Show what would be done without doing it"),
+                arg!(-p --profile <PROFILE> ... "Profiles to import, picked by hostname if not set"),
             ])
     }
 }
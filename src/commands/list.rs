@@ -1,30 +1,247 @@
-use super::Command;
-use crate::dirs::Dirs;
-use anyhow::Result;
-use clap::{command, ArgMatches};
-use std::fs;
+use super::{format::Format, paint, size, Command};
+use crate::{dirs::Dirs, repository::FileStatus, Expand, Repository};
+use anyhow::{Error, Result};
+use clap::{arg, command, ArgMatches};
+use serde::Serialize;
+use std::{collections::BTreeMap, fs};
+use termion::style;
 
 #[derive(Debug)]
 pub struct List;
 
-impl Command for List {
-    fn run(_matches: &ArgMatches) -> Result<()> {
-        let path = Dirs::Data.path();
-        let exports = fs::read_dir(path)?;
+/// What `list` shows about one export
+#[derive(Serialize)]
+struct Summary {
+    name: String,
+    files: usize,
+    /// Bytes taken by `files/`
+    size: u64,
+    last_commit: Option<String>,
+    remote: Option<String>,
+    /// Plain tracked files that differ from the live system, `None`
+    /// when they couldn't be compared
+    drift: Option<usize>,
+}
+
+/// What `list <NAME>` shows about one file
+#[derive(Serialize)]
+struct Entry {
+    path: String,
+    /// `None` for files of profiles this host doesn't use
+    status: Option<FileStatus>,
+    size: u64,
+    template: bool,
+    secret: bool,
+    profiles: Vec<String>,
+}
+
+impl Summary {
+    /// Whatever can't be read shows up as unknown rather than
+    /// hiding every other export
+    fn new(name: String, repository: &Repository) -> Self {
+        let config = &repository.config;
+
+        let size = Dirs::Files(config)
+            .path()
+            .expand()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|file| fs::metadata(file).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+
+        let remotes = repository.remotes().unwrap_or_default();
+        let remote = remotes
+            .iter()
+            .find(|(name, _)| name == "origin")
+            .or(remotes.first())
+            .map(|(_, url)| url.clone());
+
+        let last_commit = repository
+            .last_commit()
+            .ok()
+            .flatten()
+            .map(|revision| revision.date);
+
+        Self {
+            name,
+            files: config.tracked().len(),
+            size,
+            last_commit,
+            remote,
+            drift: config.drift().ok(),
+        }
+    }
+}
+
+/// Every export in the data directory, skipping whatever isn't one
+fn exports() -> Result<Vec<Summary>> {
+    let path = Dirs::Data.path();
+
+    if !path.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut summaries = vec![];
+
+    for entry in fs::read_dir(path)?.flatten() {
+        let path = entry.path();
+
+        let Ok(repository) = Repository::open(&path) else {
+            continue;
+        };
+
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        summaries.push(Summary::new(name, &repository));
+    }
+
+    summaries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(summaries)
+}
+
+impl List {
+    fn all(format: Option<Format>) -> Result<()> {
+        let summaries = exports()?;
 
-        exports.for_each(|export| {
-            let name = export.unwrap().file_name();
+        if let Some(format) = format {
+            return format.print(&summaries);
+        }
 
-            println!("{}", name.to_string_lossy());
-        });
+        if summaries.is_empty() {
+            println!("No exports yet, create one with `cup export <NAME> create`");
+            return Ok(());
+        }
+
+        let width = summaries
+            .iter()
+            .map(|s| s.name.len())
+            .max()
+            .unwrap_or(0)
+            .max(4);
+
+        println!(
+            "{}{:<width$}  {:>5}  {:>10}  {:<19}  {:<10}  REMOTE{}",
+            paint(style::Bold),
+            "NAME",
+            "FILES",
+            "SIZE",
+            "LAST COMMIT",
+            "DRIFT",
+            paint(style::Reset)
+        );
+
+        for summary in summaries {
+            let drift = match summary.drift {
+                Some(0) => "in sync".into(),
+                Some(count) => format!("{count} changed"),
+                None => "unknown".into(),
+            };
+
+            println!(
+                "{:<width$}  {:>5}  {:>10}  {:<19}  {:<10}  {}",
+                summary.name,
+                summary.files,
+                size(summary.size),
+                summary.last_commit.as_deref().unwrap_or("never"),
+                drift,
+                summary.remote.as_deref().unwrap_or("-"),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Every file `name` tracks, with how it compares to the live one
+    fn one(name: &str, format: Option<Format>) -> Result<()> {
+        let path = Dirs::Data.join(name);
+
+        if !path.is_dir() {
+            return Err(Error::msg(format!("There is no export named '{name}'")));
+        }
+
+        let repository = Repository::open(&path)?;
+        let config = &repository.config;
+
+        let statuses: BTreeMap<_, _> = config.status()?.into_iter().collect();
+
+        let entries: Vec<_> = config
+            .tracked()
+            .into_iter()
+            .map(|file| Entry {
+                path: file.user_path(),
+                status: statuses.get(file).copied(),
+                size: fs::metadata(Dirs::Files(config).join(file.to_string()))
+                    .map(|m| m.len())
+                    .unwrap_or_default(),
+                template: config.is_template(file),
+                secret: config.is_secret(file),
+                profiles: config
+                    .profiles
+                    .iter()
+                    .filter(|(_, profile)| profile.files.contains(file))
+                    .map(|(name, _)| name.clone())
+                    .collect(),
+            })
+            .collect();
+
+        if let Some(format) = format {
+            return format.print(&entries);
+        }
+
+        if entries.is_empty() {
+            println!("No files tracked in '{name}'");
+        }
+
+        for entry in entries {
+            let status = match entry.status {
+                Some(status) => status.to_string(),
+                None => "other profile".into(),
+            };
+
+            let mut tags = vec![];
+
+            if entry.template {
+                tags.push("template".to_string());
+            }
+            if entry.secret {
+                tags.push("secret".to_string());
+            }
+            for profile in &entry.profiles {
+                tags.push(format!("profile {profile}"));
+            }
+
+            let line = format!(
+                "{status:<20}{:>10}  {}  {}",
+                size(entry.size),
+                entry.path,
+                tags.join(", ")
+            );
+
+            println!("{}", line.trim_end());
+        }
 
         Ok(())
     }
 }
 
+impl Command for List {
+    fn run(matches: &ArgMatches) -> Result<()> {
+        let format = Format::from(matches);
+
+        match matches.get_one::<String>("NAME") {
+            Some(name) => Self::one(name, format),
+            None => Self::all(format),
+        }
+    }
+}
+
 #[allow(clippy::from_over_into)]
 impl Into<clap::Command> for List {
     fn into(self) -> clap::Command {
-        command!("list").about("List dotfiles")
+        command!("list")
+            .about("List exports, or the files of one of them")
+            .arg(arg!([NAME] "Export name"))
     }
 }
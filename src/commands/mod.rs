@@ -1,14 +1,57 @@
 use anyhow::Result;
 use clap::ArgMatches;
+use std::{
+    fmt,
+    io::{self, IsTerminal},
+};
 
+mod diff;
 mod export;
+mod format;
 mod import;
 mod list;
+mod picker;
+mod pull;
+mod restore;
+mod scan;
+mod status;
 
+pub use diff::Diff;
 pub use export::Export;
 pub use import::Import;
 pub use list::List;
+pub use pull::Pull;
+pub use restore::Restore;
+pub use scan::Scan;
+pub use status::Status;
 
 pub trait Command {
     fn run(matches: &ArgMatches) -> Result<()>;
 }
+
+/// The escape sequence `code`, nothing when stdout isn't a terminal
+/// so redirected output stays plain
+fn paint(code: impl fmt::Display) -> String {
+    match io::stdout().is_terminal() {
+        true => code.to_string(),
+        false => String::new(),
+    }
+}
+
+/// `bytes` the way `ls -h` would put it
+fn size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", UNITS[unit]),
+    }
+}
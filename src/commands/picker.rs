@@ -0,0 +1,261 @@
+use crate::{dirs::Dirs, Config, Ignore};
+use anyhow::Result;
+use dialoguer::{MultiSelect, Select};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use termion::style;
+
+const PREVIEW_LINES: usize = 20;
+
+/// Dotfiles most setups have besides the `~/.*rc` ones and `~/.config`
+const WELL_KNOWN: &[&str] = &[
+    ".profile",
+    ".bash_profile",
+    ".gitconfig",
+    ".tmux.conf",
+    ".ssh/config",
+];
+
+/// `path` with the home directory written as `~`
+pub(super) fn display(path: &Path) -> String {
+    match path.strip_prefix(Dirs::Home.path()) {
+        Ok(relative) if relative.as_os_str().is_empty() => "~".into(),
+        Ok(relative) => format!("~/{}", relative.display()),
+        Err(_) => path.display().to_string(),
+    }
+}
+
+fn preview(path: &Path) -> Result<()> {
+    let contents = fs::read(path)?;
+
+    println!("{}{}{}", style::Bold, display(path), style::Reset);
+
+    if contents.contains(&0) {
+        println!("Binary file, {} bytes", contents.len());
+        return Ok(());
+    }
+
+    let text = String::from_utf8_lossy(&contents);
+    let lines: Vec<_> = text.lines().collect();
+
+    for line in lines.iter().take(PREVIEW_LINES) {
+        println!("{line}");
+    }
+
+    if lines.len() > PREVIEW_LINES {
+        println!("... {} more lines", lines.len() - PREVIEW_LINES);
+    }
+
+    Ok(())
+}
+
+/// Browses the filesystem from the home directory so files
+/// and directories can be ticked instead of typed out
+pub struct Picker {
+    ignore: Ignore,
+    /// Live paths of every file and directory the export tracks
+    tracked: Vec<PathBuf>,
+    selected: Vec<PathBuf>,
+}
+
+impl Picker {
+    pub fn new(config: &Config) -> Result<Self> {
+        let mut tracked: Vec<_> = config.tracked().iter().map(|f| f.stored_path()).collect();
+
+        tracked.extend(config.directories.iter().map(|dir| dir.stored_path()));
+
+        for profile in config.profiles.values() {
+            tracked.extend(profile.directories.iter().map(|dir| dir.stored_path()));
+        }
+
+        Ok(Self {
+            ignore: Ignore::new(&config.ignore)?,
+            tracked,
+            selected: vec![],
+        })
+    }
+
+    /// What the user ticked, nothing if they backed out with escape
+    pub fn run(mut self) -> Result<Vec<PathBuf>> {
+        let mut dir = Dirs::Home.path();
+
+        loop {
+            let entries = self.entries(&dir)?;
+
+            let mut items = vec![
+                format!("Done, add {} selected", self.selected.len()),
+                "Suggestions".into(),
+                "../".into(),
+            ];
+
+            items.extend(entries.iter().map(|path| {
+                let mark = if self.selected.contains(path) {
+                    "[x]"
+                } else {
+                    "[ ]"
+                };
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let slash = if path.is_dir() { "/" } else { "" };
+
+                self.label(path, format!("{mark} {name}{slash}"))
+            }));
+
+            let Some(choice) = Select::new()
+                .with_prompt(display(&dir))
+                .items(&items)
+                .default(0)
+                .interact_opt()?
+            else {
+                return Ok(vec![]);
+            };
+
+            match choice {
+                0 => return Ok(self.selected),
+                1 => self.suggestions()?,
+                2 => {
+                    if let Some(parent) = dir.parent() {
+                        dir = parent.into();
+                    }
+                }
+                i => {
+                    if let Some(open) = self.entry(&entries[i - 3])? {
+                        dir = open;
+                    }
+                }
+            }
+        }
+    }
+
+    /// `name` followed by whether the export already has `path`
+    fn label(&self, path: &Path, name: String) -> String {
+        if self.tracked.iter().any(|tracked| path.starts_with(tracked)) {
+            format!("{name} (tracked)")
+        } else if self.tracked.iter().any(|tracked| tracked.starts_with(path)) {
+            format!("{name} (partly tracked)")
+        } else {
+            name
+        }
+    }
+
+    /// Everything inside `dir` the ignore rules don't hide, directories first
+    fn entries(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let ignore = self.ignore.enter(dir);
+
+        let mut entries: Vec<_> = fs::read_dir(dir)?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| !ignore.is_ignored(path, path.is_dir()))
+            .collect();
+
+        entries.sort_by_key(|path| (!path.is_dir(), path.file_name().map(|n| n.to_owned())));
+
+        Ok(entries)
+    }
+
+    fn toggle(&mut self, path: &Path) {
+        match self.selected.iter().position(|selected| selected == path) {
+            Some(index) => {
+                self.selected.remove(index);
+            }
+            None => self.selected.push(path.into()),
+        }
+    }
+
+    /// Asks what to do with `path`, returning it when it's a directory to open
+    fn entry(&mut self, path: &Path) -> Result<Option<PathBuf>> {
+        let toggle = match self.selected.iter().any(|selected| selected == path) {
+            true => "Unselect",
+            false => "Select",
+        };
+
+        let first = if path.is_dir() { "Open" } else { "Preview" };
+
+        let choice = Select::new()
+            .with_prompt(display(path))
+            .items(&[first, toggle, "Back"])
+            .default(0)
+            .interact_opt()?;
+
+        match choice {
+            Some(0) if path.is_dir() => return Ok(Some(path.into())),
+            Some(0) => {
+                if let Err(err) = preview(path) {
+                    println!("Can't preview '{}': {err}", display(path));
+                }
+            }
+            Some(1) => self.toggle(path),
+            _ => {}
+        }
+
+        Ok(None)
+    }
+
+    /// Every `~/.*rc`, everything in `~/.config` and a few well known dotfiles
+    fn suggested(&self) -> Result<Vec<PathBuf>> {
+        let home = Dirs::Home.path();
+
+        let mut found: Vec<_> = fs::read_dir(&home)?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+                path.is_file() && name.starts_with('.') && name.ends_with("rc")
+            })
+            .collect();
+
+        found.extend(
+            WELL_KNOWN
+                .iter()
+                .map(|name| home.join(name))
+                .filter(|path| path.exists()),
+        );
+
+        let config = home.join(".config");
+
+        if config.is_dir() {
+            found.extend(self.entries(&config)?);
+        }
+
+        found.retain(|path| !self.ignore.is_ignored(path, path.is_dir()));
+        found.sort();
+        found.dedup();
+
+        Ok(found)
+    }
+
+    fn suggestions(&mut self) -> Result<()> {
+        let suggested = self.suggested()?;
+
+        if suggested.is_empty() {
+            println!("No well known dotfiles found");
+            return Ok(());
+        }
+
+        let items: Vec<_> = suggested
+            .iter()
+            .map(|path| {
+                let slash = if path.is_dir() { "/" } else { "" };
+                let label = self.label(path, format!("{}{slash}", display(path)));
+
+                (label, self.selected.contains(path))
+            })
+            .collect();
+
+        let Some(chosen) = MultiSelect::new()
+            .with_prompt("Suggestions")
+            .items_checked(&items)
+            .interact_opt()?
+        else {
+            return Ok(());
+        };
+
+        for (index, path) in suggested.iter().enumerate() {
+            if chosen.contains(&index) != self.selected.contains(path) {
+                self.toggle(path);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+use super::{format::Format, Command};
+use crate::{dirs::Dirs, Repository};
+use anyhow::Result;
+use clap::{arg, command, ArgMatches};
+use serde::Serialize;
+
+#[derive(Debug)]
+pub struct Pull;
+
+#[derive(Serialize)]
+struct Pulled<'a> {
+    export: &'a str,
+    remote: &'a str,
+    /// Whether there was anything to fast-forward to
+    updated: bool,
+}
+
+impl Command for Pull {
+    fn run(matches: &ArgMatches) -> Result<()> {
+        let name = matches.get_one::<String>("NAME").unwrap();
+        let remote = matches.get_one::<String>("REMOTE").unwrap();
+
+        let path = Dirs::Data.join(name);
+        let mut repository = Repository::open(&path)?;
+
+        let updated = repository.pull(remote)?;
+
+        if let Some(format) = Format::from(matches) {
+            return format.print(&Pulled {
+                export: name,
+                remote,
+                updated,
+            });
+        }
+
+        match updated {
+            true => println!("Updated '{name}' from '{remote}'"),
+            false => println!("'{name}' is already up to date"),
+        }
+
+        Ok(())
+    }
+}
+
+#[allow(clippy::from_over_into)]
+impl Into<clap::Command> for Pull {
+    fn into(self) -> clap::Command {
+        command!("pull")
+            .about("Update an export from its remote")
+            .args([
+                arg!(<NAME> "Export name"),
+                arg!([REMOTE] "Remote name").default_value("origin"),
+            ])
+    }
+}
@@ -0,0 +1,84 @@
+use super::{format::Format, Command};
+use crate::repository::Backup;
+use anyhow::Result;
+use clap::{arg, command, ArgMatches};
+use serde::Serialize;
+
+#[derive(Debug)]
+pub struct Restore;
+
+/// An import run the way `--format` prints it
+#[derive(Serialize)]
+struct Run<'a> {
+    id: &'a str,
+    date: &'a str,
+    export: &'a str,
+    files: Vec<String>,
+}
+
+impl<'a> From<&'a Backup> for Run<'a> {
+    fn from(backup: &'a Backup) -> Self {
+        Self {
+            id: &backup.id,
+            date: &backup.date,
+            export: &backup.export,
+            files: backup
+                .entries
+                .iter()
+                .map(|entry| entry.file.user_path())
+                .collect(),
+        }
+    }
+}
+
+impl Command for Restore {
+    fn run(matches: &ArgMatches) -> Result<()> {
+        let format = Format::from(matches);
+        let quiet = *matches.get_one::<bool>("quiet").unwrap() || format.is_some();
+
+        if *matches.get_one::<bool>("list").unwrap() {
+            let backups = Backup::list()?;
+
+            if let Some(format) = format {
+                let runs: Vec<_> = backups.iter().map(Run::from).collect();
+
+                return format.print(&runs);
+            }
+
+            if backups.is_empty() {
+                println!("There are no backups");
+            }
+
+            for backup in backups {
+                println!("{backup}");
+            }
+
+            return Ok(());
+        }
+
+        let backup = match matches.get_one::<String>("RUN_ID") {
+            Some(id) => Backup::open(id)?,
+            None => Backup::latest()?,
+        };
+
+        backup.clone().restore(quiet)?;
+
+        match format {
+            Some(format) => format.print(&Run::from(&backup)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[allow(clippy::from_over_into)]
+impl Into<clap::Command> for Restore {
+    fn into(self) -> clap::Command {
+        command!("restore")
+            .about("Undo an import, latest one by default")
+            .args([
+                arg!([RUN_ID] "Import run to undo"),
+                arg!(-l --list "List the import runs that can be undone").conflicts_with("RUN_ID"),
+                arg!(-q --quiet "Do not output any information"),
+            ])
+    }
+}
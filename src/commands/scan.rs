@@ -0,0 +1,192 @@
+use super::{format::Format, paint, picker::display, size, Command};
+use crate::{
+    dirs::Dirs,
+    path::scan::{scan, Candidate},
+    Ignore, Repository,
+};
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use clap::{arg, command, ArgMatches};
+use dialoguer::MultiSelect;
+use serde::Serialize;
+use std::path::PathBuf;
+use termion::{color, style};
+
+#[derive(Debug)]
+pub struct Scan;
+
+/// A candidate the way `--format` prints it
+#[derive(Serialize)]
+struct Found {
+    path: String,
+    kind: &'static str,
+    size: u64,
+    modified: String,
+    secrets: bool,
+    large: bool,
+}
+
+impl From<&Candidate> for Found {
+    fn from(candidate: &Candidate) -> Self {
+        let modified: DateTime<Local> = candidate.modified.into();
+
+        Self {
+            path: candidate.path.display().to_string(),
+            kind: candidate.kind,
+            size: candidate.size,
+            modified: modified.format("%Y-%m-%d %H:%M:%S").to_string(),
+            secrets: candidate.secrets,
+            large: candidate.is_large(),
+        }
+    }
+}
+
+/// One table row, without colors so it can be reused as a selection item
+fn row(candidate: &Candidate, tracked: bool) -> String {
+    let modified: DateTime<Local> = candidate.modified.into();
+
+    let mut flags = vec![];
+
+    if candidate.secrets {
+        flags.push("secrets?");
+    }
+    if candidate.is_large() {
+        flags.push("large");
+    }
+    if tracked {
+        flags.push("tracked");
+    }
+
+    format!(
+        "{:<9}{:<40}{:>10}  {}  {}",
+        candidate.kind,
+        display(&candidate.path),
+        size(candidate.size),
+        modified.format("%Y-%m-%d"),
+        flags.join(", "),
+    )
+    .trim_end()
+    .to_string()
+}
+
+impl Command for Scan {
+    fn run(matches: &ArgMatches) -> Result<()> {
+        let export = matches.get_one::<String>("add");
+        let format = Format::from(matches);
+        let profile = matches.get_one::<String>("profile").map(String::as_str);
+
+        let mut repository = match export {
+            Some(name) => Some(Repository::open(&Dirs::Data.join(name))?),
+            None => None,
+        };
+
+        let (ignore, tracked) = match &repository {
+            Some(repository) => {
+                let config = &repository.config;
+                let mut tracked: Vec<PathBuf> =
+                    config.tracked().iter().map(|f| f.stored_path()).collect();
+
+                tracked.extend(config.directories.iter().map(|dir| dir.stored_path()));
+
+                (Ignore::new(&config.ignore)?, tracked)
+            }
+            None => (Ignore::new(&[])?, vec![]),
+        };
+
+        let is_tracked = |candidate: &Candidate| {
+            tracked
+                .iter()
+                .any(|tracked| candidate.path.starts_with(tracked))
+        };
+
+        let candidates = scan(&ignore);
+
+        if let (Some(format), None) = (format, &repository) {
+            let found: Vec<_> = candidates.iter().map(Found::from).collect();
+
+            return format.print(&found);
+        }
+
+        if candidates.is_empty() {
+            println!("No known configuration files found");
+            return Ok(());
+        }
+
+        let Some(repository) = repository.as_mut() else {
+            for (index, candidate) in candidates.iter().enumerate() {
+                let colored = match candidate.secrets {
+                    true => paint(color::Fg(color::Red)),
+                    false if candidate.is_large() => paint(color::Fg(color::Yellow)),
+                    false => paint(color::Fg(color::Reset)),
+                };
+
+                println!(
+                    "{colored}{:>3}  {}{}",
+                    index + 1,
+                    row(candidate, false),
+                    paint(style::Reset)
+                );
+            }
+
+            return Ok(());
+        };
+
+        let offered: Vec<_> = candidates.iter().filter(|c| !is_tracked(c)).collect();
+
+        if offered.is_empty() {
+            println!("Everything found is already tracked");
+            return Ok(());
+        }
+
+        // secrets and caches have to be ticked on purpose
+        let items: Vec<_> = offered
+            .iter()
+            .map(|c| (row(c, false), !c.secrets && !c.is_large()))
+            .collect();
+
+        let Some(chosen) = MultiSelect::new()
+            .with_prompt("Add to the export")
+            .items_checked(&items)
+            .report(false)
+            .interact_opt()?
+        else {
+            return Ok(());
+        };
+
+        let paths: Vec<_> = chosen.iter().map(|&i| offered[i].path.clone()).collect();
+
+        if paths.is_empty() {
+            println!("There are no files to add");
+            return Ok(());
+        }
+
+        repository.config.add_paths(&paths, profile)?;
+        let plan = repository.config.save(
+            None,
+            *matches.get_one::<bool>("allow-secrets").unwrap(),
+            format.is_some(),
+        )?;
+
+        match format {
+            Some(format) => format.print(&plan),
+            None => Ok(()),
+        }
+    }
+}
+
+#[allow(clippy::from_over_into)]
+impl Into<clap::Command> for Scan {
+    fn into(self) -> clap::Command {
+        command!("scan")
+            .about("Look for configuration files worth exporting")
+            .arg(arg!(-a --add <EXPORT> "Choose which of the found files to add to an export"))
+            .arg(
+                arg!(-p --profile <PROFILE> "Add them to a profile instead of the shared files")
+                    .requires("add"),
+            )
+            .arg(
+                arg!(--"allow-secrets" "Commit files even if they look like they hold secrets")
+                    .requires("add"),
+            )
+    }
+}
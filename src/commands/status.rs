@@ -0,0 +1,71 @@
+use super::{format::Format, paint, Command};
+use crate::{dirs::Dirs, repository::FileStatus, Repository};
+use anyhow::Result;
+use clap::{arg, command, ArgMatches};
+use serde::Serialize;
+use termion::{color, style};
+
+#[derive(Debug)]
+pub struct Status;
+
+#[derive(Serialize)]
+struct Entry {
+    path: String,
+    status: FileStatus,
+}
+
+impl Command for Status {
+    fn run(matches: &ArgMatches) -> Result<()> {
+        let name = matches.get_one::<String>("NAME").unwrap();
+
+        let path = Dirs::Data.join(name);
+        let repository = Repository::open(&path)?;
+
+        let mut entries = repository.config.status()?;
+        entries.sort_by(|(a, a_status), (b, b_status)| (a_status, a).cmp(&(b_status, b)));
+
+        if let Some(format) = Format::from(matches) {
+            let entries: Vec<_> = entries
+                .into_iter()
+                .map(|(file, status)| Entry {
+                    path: file.user_path(),
+                    status,
+                })
+                .collect();
+
+            return format.print(&entries);
+        }
+
+        if entries.is_empty() {
+            println!("No files tracked in '{name}'");
+        }
+
+        for (file, status) in entries {
+            let colored = match status {
+                FileStatus::Unchanged => paint(color::Fg(color::Reset)),
+                FileStatus::ModifiedLocally => paint(color::Fg(color::Yellow)),
+                FileStatus::ModifiedInExport => paint(color::Fg(color::Cyan)),
+                FileStatus::MissingLocally
+                | FileStatus::MissingInExport
+                | FileStatus::Unrenderable => paint(color::Fg(color::Red)),
+            };
+
+            println!(
+                "{colored}{status:<20}{}{}",
+                paint(style::Reset),
+                file.user_path()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[allow(clippy::from_over_into)]
+impl Into<clap::Command> for Status {
+    fn into(self) -> clap::Command {
+        command!("status")
+            .about("Show differences between an export and your system")
+            .arg(arg!(<NAME> "Export name"))
+    }
+}
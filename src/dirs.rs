@@ -11,6 +11,8 @@ pub enum Dirs<'a> {
     Home,
     Data,
     Cache,
+    Config,
+    Backups,
     Root,
     Files(&'a Config),
 }
@@ -25,6 +27,11 @@ impl Dirs<'_> {
             Self::Root => PathBuf::from("/"),
             Self::Data => project.data_local_dir().to_owned(),
             Self::Cache => project.cache_dir().to_owned(),
+            Self::Config => project.config_dir().to_owned(),
+            Self::Backups => project
+                .state_dir()
+                .unwrap_or_else(|| project.cache_dir())
+                .join("backups"),
             Self::Files(config) => config.path.parent().unwrap().join("files"),
         }
     }
@@ -4,5 +4,5 @@ mod path;
 pub mod repository;
 
 use dirs::Dirs;
-pub use path::expand::Expand;
+pub use path::{expand::Expand, ignore::Ignore};
 pub use repository::{Config, Repository};
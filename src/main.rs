@@ -1,5 +1,5 @@
 use anyhow::Result;
-use clap::command;
+use clap::{arg, command};
 use cup::commands::*;
 
 fn main() -> Result<()> {
@@ -9,6 +9,11 @@ fn main() -> Result<()> {
         Some(("import", matches)) => Import::run(matches),
         Some(("export", matches)) => Export::run(matches),
         Some(("list", matches)) => List::run(matches),
+        Some(("status", matches)) => Status::run(matches),
+        Some(("diff", matches)) => Diff::run(matches),
+        Some(("restore", matches)) => Restore::run(matches),
+        Some(("pull", matches)) => Pull::run(matches),
+        Some(("scan", matches)) => Scan::run(matches),
         _ => Ok(()),
     }
 }
@@ -17,7 +22,20 @@ pub fn parse_args() -> clap::Command {
     let app = command!()
         .about("I bet you can't hold it")
         .disable_help_subcommand(true)
-        .subcommand_required(true);
+        .subcommand_required(true)
+        .arg(
+            arg!(--format <FORMAT> "Print results as json or yaml instead of text")
+                .value_parser(["text", "json", "yaml"])
+                .default_value("text")
+                .global(true),
+        );
 
-    app.subcommand(Import).subcommand(Export).subcommand(List)
+    app.subcommand(Import)
+        .subcommand(Export)
+        .subcommand(List)
+        .subcommand(Status)
+        .subcommand(Diff)
+        .subcommand(Restore)
+        .subcommand(Pull)
+        .subcommand(Scan)
 }
@@ -0,0 +1,35 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::Path,
+};
+
+/// Checks whether two files hold exactly the same bytes,
+/// bailing out early when their sizes already differ
+pub fn same_contents<P: AsRef<Path>, Q: AsRef<Path>>(a: P, b: Q) -> io::Result<bool> {
+    let (a, b) = (a.as_ref(), b.as_ref());
+
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+
+    let mut a = File::open(a)?;
+    let mut b = File::open(b)?;
+
+    let mut buf_a = [0; 8192];
+    let mut buf_b = [0; 8192];
+
+    loop {
+        let read = a.read(&mut buf_a)?;
+
+        if read == 0 {
+            return Ok(true);
+        }
+
+        b.read_exact(&mut buf_b[..read])?;
+
+        if buf_a[..read] != buf_b[..read] {
+            return Ok(false);
+        }
+    }
+}
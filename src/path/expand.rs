@@ -1,3 +1,4 @@
+use super::ignore::Ignore;
 use std::{
     fs, io,
     path::{Path, PathBuf},
@@ -5,9 +6,22 @@ use std::{
 
 pub trait Expand {
     fn expand(&self) -> io::Result<Vec<PathBuf>>;
+
+    /// Like `expand`, leaving out whatever `ignore` matches
+    fn expand_ignoring(&self, ignore: &Ignore) -> io::Result<Vec<PathBuf>>;
 }
 
 impl Expand for PathBuf {
+    fn expand(&self) -> io::Result<Vec<PathBuf>> {
+        self.as_path().expand()
+    }
+
+    fn expand_ignoring(&self, ignore: &Ignore) -> io::Result<Vec<PathBuf>> {
+        self.as_path().expand_ignoring(ignore)
+    }
+}
+
+impl Expand for Path {
     fn expand(&self) -> io::Result<Vec<PathBuf>> {
         let mut files = vec![];
 
@@ -15,24 +29,30 @@ impl Expand for PathBuf {
             files.push(self.into());
         } else if self.is_dir() {
             for entry in fs::read_dir(self)? {
-                let mut inner = Self::expand(&entry?.path())?;
+                let mut inner = entry?.path().expand()?;
                 files.append(&mut inner);
             }
         }
 
         Ok(files)
     }
-}
 
-impl Expand for Path {
-    fn expand(&self) -> io::Result<Vec<PathBuf>> {
+    fn expand_ignoring(&self, ignore: &Ignore) -> io::Result<Vec<PathBuf>> {
         let mut files = vec![];
 
         if self.is_file() {
             files.push(self.into());
         } else if self.is_dir() {
+            let ignore = ignore.enter(self);
+
             for entry in fs::read_dir(self)? {
-                let mut inner = Self::expand(&entry?.path())?;
+                let path = entry?.path();
+
+                if ignore.is_ignored(&path, path.is_dir()) {
+                    continue;
+                }
+
+                let mut inner = path.expand_ignoring(&ignore)?;
                 files.append(&mut inner);
             }
         }
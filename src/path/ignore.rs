@@ -0,0 +1,69 @@
+use crate::Dirs;
+use ::ignore::gitignore::{Gitignore, GitignoreBuilder};
+use anyhow::Result;
+use std::path::Path;
+
+/// Name of the per-directory ignore file, read while expanding directories
+pub const IGNORE_FILE: &str = ".cupignore";
+
+/// Gitignore-style rules deciding what directory expansion leaves out
+#[derive(Debug, Clone, Default)]
+pub struct Ignore {
+    rules: Vec<Gitignore>,
+}
+
+impl Ignore {
+    /// The global `ignore` file in cup's config directory plus `patterns`,
+    /// both relative to the home directory
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let mut builder = GitignoreBuilder::new(Dirs::Home.path());
+
+        let global = Dirs::Config.join("ignore");
+
+        if global.exists() {
+            if let Some(err) = builder.add(global) {
+                return Err(err.into());
+            }
+        }
+
+        for pattern in patterns {
+            builder.add_line(None, pattern)?;
+        }
+
+        Ok(Self {
+            rules: vec![builder.build()?],
+        })
+    }
+
+    /// The same rules plus the `.cupignore` inside `dir`, if there is one
+    pub fn enter(&self, dir: &Path) -> Self {
+        let file = dir.join(IGNORE_FILE);
+
+        if !file.is_file() {
+            return self.clone();
+        }
+
+        let (rules, _) = Gitignore::new(file);
+        let mut ignore = self.clone();
+        ignore.rules.push(rules);
+
+        ignore
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        // the innermost rules win, like with nested .gitignore files
+        for rules in self.rules.iter().rev() {
+            let matched = rules.matched(path, is_dir);
+
+            if matched.is_ignore() {
+                return true;
+            }
+
+            if matched.is_whitelist() {
+                return false;
+            }
+        }
+
+        false
+    }
+}
@@ -0,0 +1,113 @@
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// A line that looks like it holds something that shouldn't be committed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leak {
+    pub kind: &'static str,
+    pub line: usize,
+}
+
+const PATTERNS: &[(&str, &str)] = &[
+    ("private key", r"-----BEGIN [A-Z ]*PRIVATE KEY-----"),
+    ("AWS access key", r"\bAKIA[0-9A-Z]{16}\b"),
+    ("AWS secret key", r"(?i)aws_secret_access_key\s*[:=]\s*\S+"),
+    ("GitHub token", r"\bgh[pousr]_[A-Za-z0-9]{36,}"),
+    ("Slack token", r"\bxox[abprs]-[A-Za-z0-9-]{10,}"),
+    (
+        "password",
+        r#"(?i)\b(password|secret|token|api[_-]?key)\b\s*[:=]\s*["']?[\w+/!@#%^.-]{4,}"#,
+    ),
+    (
+        "password in url",
+        r"[a-zA-Z][a-zA-Z0-9+.-]*://[^/\s:@]+:[^/\s@]+@",
+    ),
+    (
+        "netrc password",
+        r"(?i)^\s*(machine\s+\S+\s+.*)?password\s+\S+",
+    ),
+];
+
+/// Anything this long that looks random is likely a key,
+/// even when nothing around it says so
+const RANDOM_LENGTH: usize = 32;
+
+/// Bits per character, hex tops out at 4 so hashes aren't flagged
+const RANDOM_ENTROPY: f64 = 4.5;
+
+fn patterns() -> &'static [(&'static str, Regex)] {
+    static COMPILED: OnceLock<Vec<(&str, Regex)>> = OnceLock::new();
+
+    COMPILED.get_or_init(|| {
+        PATTERNS
+            .iter()
+            .map(|(kind, pattern)| (*kind, Regex::new(pattern).unwrap()))
+            .collect()
+    })
+}
+
+fn random_tokens() -> &'static Regex {
+    static COMPILED: OnceLock<Regex> = OnceLock::new();
+
+    COMPILED.get_or_init(|| Regex::new(&format!("[A-Za-z0-9+/]{{{RANDOM_LENGTH},}}=*")).unwrap())
+}
+
+/// Shannon entropy of `token` in bits per character
+fn entropy(token: &str) -> f64 {
+    let mut counts = [0usize; 256];
+
+    for byte in token.bytes() {
+        counts[byte as usize] += 1;
+    }
+
+    let length = token.len() as f64;
+
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / length;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn looks_random(token: &str) -> bool {
+    token.bytes().any(|b| b.is_ascii_uppercase())
+        && token.bytes().any(|b| b.is_ascii_lowercase())
+        && token.bytes().any(|b| b.is_ascii_digit())
+        && entropy(token) > RANDOM_ENTROPY
+}
+
+/// Every line of `contents` matching one of the secret patterns,
+/// binary contents are never looked at
+pub fn find_leaks(contents: &[u8]) -> Vec<Leak> {
+    if contents.contains(&0) {
+        return vec![];
+    }
+
+    let text = String::from_utf8_lossy(contents);
+    let mut leaks = vec![];
+
+    for (index, line) in text.lines().enumerate() {
+        let kind = patterns()
+            .iter()
+            .find(|(_, regex)| regex.is_match(line))
+            .map(|(kind, _)| *kind)
+            .or_else(|| {
+                random_tokens()
+                    .find_iter(line)
+                    .any(|token| looks_random(token.as_str()))
+                    .then_some("random looking key")
+            });
+
+        if let Some(kind) = kind {
+            leaks.push(Leak {
+                kind,
+                line: index + 1,
+            });
+        }
+    }
+
+    leaks
+}
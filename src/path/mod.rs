@@ -1 +1,25 @@
+pub mod compare;
 pub mod expand;
+pub mod ignore;
+pub mod leak;
+pub mod scan;
+
+use crate::Dirs;
+use std::{env::current_dir, io, path::PathBuf};
+
+/// Turns user input such as `~/.bashrc` or `./foo` into an absolute path,
+/// without checking whether it exists
+pub fn resolve(value: &str) -> io::Result<PathBuf> {
+    let path = PathBuf::from(value);
+
+    if path.is_absolute() {
+        return Ok(path);
+    }
+
+    Ok(match value {
+        "~" => Dirs::Home.path(),
+        _ if value.starts_with("~/") => Dirs::Home.join(&value[2..]),
+        _ if value.starts_with("./") => current_dir()?.join(&value[2..]),
+        _ => current_dir()?.join(value),
+    })
+}
@@ -0,0 +1,169 @@
+use super::{expand::Expand, ignore::Ignore, leak::find_leaks, resolve};
+use crate::Dirs;
+use std::{
+    cmp::Reverse,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Past this many bytes a candidate is more likely a cache than configuration
+pub const LARGE: u64 = 1024 * 1024;
+
+/// Files bigger than this are not searched for secrets
+const MAX_SEARCHED: u64 = 256 * 1024;
+
+/// Configuration worth tracking, by kind; every other directory
+/// in `~/.config` is offered as well
+const KNOWN: &[(&str, &[&str])] = &[
+    (
+        "shell",
+        &[
+            "~/.bashrc",
+            "~/.bash_profile",
+            "~/.bash_aliases",
+            "~/.profile",
+            "~/.zshrc",
+            "~/.zprofile",
+            "~/.zshenv",
+            "~/.inputrc",
+            "~/.config/fish",
+        ],
+    ),
+    (
+        "git",
+        &["~/.gitconfig", "~/.gitignore_global", "~/.config/git"],
+    ),
+    ("ssh", &["~/.ssh/config"]),
+    (
+        "editor",
+        &[
+            "~/.vimrc",
+            "~/.config/nvim",
+            "~/.emacs",
+            "~/.emacs.d/init.el",
+            "~/.config/helix",
+            "~/.config/Code/User/settings.json",
+        ],
+    ),
+    (
+        "terminal",
+        &[
+            "~/.tmux.conf",
+            "~/.config/tmux",
+            "~/.config/alacritty",
+            "~/.config/kitty",
+            "~/.config/wezterm",
+            "~/.wezterm.lua",
+            "~/.config/foot",
+        ],
+    ),
+    (
+        "system",
+        &[
+            "/etc/hosts",
+            "/etc/fstab",
+            "/etc/environment",
+            "/etc/ssh/sshd_config",
+            "/etc/default/grub",
+            "/etc/pacman.conf",
+            "/etc/apt/sources.list",
+        ],
+    ),
+];
+
+/// A file or directory `scan` found
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub path: PathBuf,
+    pub kind: &'static str,
+    /// Bytes in every file it holds
+    pub size: u64,
+    /// When its most recently changed file was
+    pub modified: SystemTime,
+    /// Whether some file in it looks like it holds a secret
+    pub secrets: bool,
+}
+
+impl Candidate {
+    fn new(path: PathBuf, kind: &'static str, ignore: &Ignore) -> Option<Self> {
+        let files = path.expand_ignoring(ignore).ok()?;
+
+        let mut candidate = Self {
+            path,
+            kind,
+            size: 0,
+            modified: SystemTime::UNIX_EPOCH,
+            secrets: false,
+        };
+
+        for file in &files {
+            let Ok(metadata) = fs::metadata(file) else {
+                continue;
+            };
+
+            candidate.size += metadata.len();
+
+            if let Ok(modified) = metadata.modified() {
+                candidate.modified = candidate.modified.max(modified);
+            }
+        }
+
+        // searching a whole cache directory would take forever
+        if candidate.size <= LARGE {
+            candidate.secrets = files.iter().any(|file| {
+                fs::metadata(file).is_ok_and(|m| m.len() <= MAX_SEARCHED)
+                    && fs::read(file).is_ok_and(|contents| !find_leaks(&contents).is_empty())
+            });
+        }
+
+        Some(candidate)
+    }
+
+    pub fn is_large(&self) -> bool {
+        self.size > LARGE
+    }
+}
+
+/// Looks for the known configuration files in the home directory and
+/// `/etc`, recently changed ones first and large ones last
+pub fn scan(ignore: &Ignore) -> Vec<Candidate> {
+    let mut candidates = vec![];
+    let mut seen: Vec<PathBuf> = vec![];
+
+    // cup's own config holds the identity secrets are encrypted with
+    let skipped = |path: &Path| path.starts_with(Dirs::Config.path());
+
+    for (kind, paths) in KNOWN {
+        for path in paths.iter().filter_map(|path| resolve(path).ok()) {
+            if !path.exists() || skipped(&path) {
+                continue;
+            }
+
+            seen.push(path.clone());
+            candidates.extend(Candidate::new(path, kind, ignore));
+        }
+    }
+
+    let config = Dirs::Home.join(".config");
+
+    if let Ok(entries) = fs::read_dir(config) {
+        let ignore = ignore.enter(&Dirs::Home.join(".config"));
+
+        for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+            if !path.is_dir()
+                || skipped(&path)
+                || seen.iter().any(|seen| seen.starts_with(&path))
+                || ignore.is_ignored(&path, true)
+            {
+                continue;
+            }
+
+            candidates.extend(Candidate::new(path, "xdg", &ignore));
+        }
+    }
+
+    candidates.sort_by_key(|candidate| (candidate.is_large(), Reverse(candidate.modified)));
+
+    candidates
+}
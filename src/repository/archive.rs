@@ -0,0 +1,282 @@
+use super::{Config, Repository};
+use crate::{Dirs, Expand};
+use anyhow::{Error, Result};
+use clap::crate_name;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use git2::{TreeWalkMode, TreeWalkResult};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path, PathBuf},
+    process,
+};
+
+/// Checksums of everything in a bundle but its history, `sha256sum -c` can read it
+const CHECKSUMS: &str = "SHA256SUMS";
+
+fn checksum(path: &Path) -> Result<String> {
+    Ok(format!("{:x}", Sha256::digest(fs::read(path)?)))
+}
+
+/// What the checksums of the unpacked export at `root` list,
+/// nothing for bundles without any
+fn listed_checksums(root: &Path) -> Result<Option<BTreeMap<PathBuf, String>>> {
+    let path = root.join(CHECKSUMS);
+
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let listed = fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| line.split_once("  "))
+        .map(|(sum, file)| (PathBuf::from(file), sum.to_string()))
+        .collect();
+
+    Ok(Some(listed))
+}
+
+/// Fails unless every file of the unpacked export at `root` is listed
+/// in its checksums and matches them, bundles without any are trusted
+fn verify(root: &Path) -> Result<()> {
+    let Some(listed) = listed_checksums(root)? else {
+        return Ok(());
+    };
+
+    for (file, sum) in &listed {
+        let path = root.join(file);
+
+        if !path.is_file() || checksum(&path)? != *sum {
+            return Err(Error::msg(format!(
+                "The bundle is corrupted, '{}' doesn't match its checksum",
+                file.display()
+            )));
+        }
+    }
+
+    for file in root.join("files").expand()? {
+        let file = file.strip_prefix(root)?;
+
+        if !listed.contains_key(file) {
+            return Err(Error::msg(format!(
+                "The bundle is corrupted, '{}' has no checksum",
+                file.display()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Fails unless the latest commit of the bundled history holds exactly
+/// the files listed in the checksums, since that commit is what gets imported
+fn verify_history(root: &Path) -> Result<()> {
+    let Some(listed) = listed_checksums(root)? else {
+        return Ok(());
+    };
+
+    let repository = git2::Repository::open(root)?;
+    let tree = repository.head()?.peel_to_tree()?;
+    let config = PathBuf::from(format!("{}.yml", crate_name!()));
+
+    let mut committed = BTreeMap::new();
+    let mut failed = None;
+
+    tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        let path = Path::new(dir).join(entry.name().unwrap_or_default());
+
+        if path != config && !path.starts_with("files") {
+            return TreeWalkResult::Ok;
+        }
+
+        match entry
+            .to_object(&repository)
+            .map(|object| object.into_blob())
+        {
+            Ok(Ok(blob)) => {
+                committed.insert(path, format!("{:x}", Sha256::digest(blob.content())));
+            }
+            Ok(Err(_)) => {}
+            Err(err) => {
+                failed = Some(err);
+                return TreeWalkResult::Abort;
+            }
+        }
+
+        TreeWalkResult::Ok
+    })?;
+
+    if let Some(err) = failed {
+        return Err(err.into());
+    }
+
+    let mismatch = listed
+        .iter()
+        .find(|(file, sum)| committed.get(*file) != Some(*sum))
+        .map(|(file, _)| file)
+        .or_else(|| committed.keys().find(|file| !listed.contains_key(*file)));
+
+    match mismatch {
+        Some(file) => Err(Error::msg(format!(
+            "The bundle is corrupted, '{}' in its history doesn't match its checksum",
+            file.display()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Whether the export name from a bundle stays a single directory
+/// inside the data directory instead of pointing anywhere else
+fn is_plain_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    )
+}
+
+/// The directory holding `cup.yml` inside an unpacked bundle,
+/// either its top level or the single directory in it
+fn export_root(unpacked: &Path) -> Result<PathBuf> {
+    if Config::open(unpacked).is_ok() {
+        return Ok(unpacked.into());
+    }
+
+    let entries: Vec<_> = fs::read_dir(unpacked)?.collect::<Result<_, _>>()?;
+
+    match entries.as_slice() {
+        [entry] if Config::open(entry.path()).is_ok() => Ok(entry.path()),
+        _ => Err(Error::msg("The bundle does not contain an export")),
+    }
+}
+
+impl Repository {
+    /// Packs `cup.yml` and `files/` into a `.tar.gz` at `output` next to
+    /// their checksums, with the git history as well when `history` is set
+    pub fn bundle(&self, output: &Path, history: bool) -> Result<()> {
+        let name = Path::new(&self.config.name);
+
+        let mut entries = vec![PathBuf::from(format!("{}.yml", crate_name!()))];
+
+        for file in Dirs::Files(&self.config).path().expand()? {
+            entries.push(file.strip_prefix(&self.path)?.into());
+        }
+
+        let encoder = GzEncoder::new(fs::File::create(output)?, Compression::default());
+        let mut archive = tar::Builder::new(encoder);
+        let mut checksums = String::new();
+
+        for entry in &entries {
+            let path = self.path.join(entry);
+
+            checksums.push_str(&format!("{}  {}\n", checksum(&path)?, entry.display()));
+            archive.append_path_with_name(path, name.join(entry))?;
+        }
+
+        let mut header = tar::Header::new_gnu();
+        header.set_size(checksums.len() as u64);
+        header.set_mode(0o644);
+        archive.append_data(&mut header, name.join(CHECKSUMS), checksums.as_bytes())?;
+
+        if history {
+            archive.append_dir_all(name.join(".git"), self.path.join(".git"))?;
+        }
+
+        archive.into_inner()?.finish()?;
+
+        Ok(())
+    }
+
+    /// Imports a `.tar.gz` bundle into `dest`, keeping its history when it has one
+    pub(super) fn unpack(archive: &Path, dest: &Path) -> Result<Self> {
+        let unpacked = Dirs::Cache.join(format!("unpack-{}", process::id()));
+
+        let result = Self::unpack_into(archive, &unpacked, dest);
+        fs::remove_dir_all(&unpacked).ok();
+
+        result
+    }
+
+    fn unpack_into(archive: &Path, unpacked: &Path, dest: &Path) -> Result<Self> {
+        fs::create_dir_all(unpacked)?;
+        tar::Archive::new(GzDecoder::new(fs::File::open(archive)?)).unpack(unpacked)?;
+
+        let root = export_root(unpacked)?;
+        verify(&root)?;
+        let config = Config::open(&root)?;
+
+        if !is_plain_name(&config.name) {
+            return Err(Error::msg(format!(
+                "The bundle's export name '{}' is not a plain directory name",
+                config.name
+            )));
+        }
+
+        let dest = dest.join(&config.name);
+        let has_history = root.join(".git").is_dir();
+
+        if has_history {
+            verify_history(&root)?;
+        }
+
+        if dest.exists() {
+            let mut repository = Self::open(&dest)?;
+
+            if repository.config.id != config.id {
+                return Err(Error::msg(format!(
+                    "'{}' already exists and is a different export",
+                    dest.display()
+                )));
+            }
+
+            if !has_history {
+                return Err(Error::msg(format!(
+                    "'{}' already exists and the bundle has no history to fast-forward it with",
+                    dest.display()
+                )));
+            }
+
+            repository.pull_path(&root, "bundle")?;
+
+            return Ok(repository);
+        }
+
+        if has_history {
+            let source = git2::Repository::open(&root)?;
+            let repository = git2::Repository::clone(&root.display().to_string(), &dest)?;
+
+            // keep pointing at wherever the export was pushed to, not at the unpacked copy
+            repository.remote_delete("origin")?;
+
+            for name in source.remotes()?.iter().flatten() {
+                if let Some(url) = source.find_remote(name)?.url() {
+                    repository.remote(name, url)?;
+                }
+            }
+
+            return Self::open(&dest);
+        }
+
+        for file in root.expand()? {
+            if file == root.join(CHECKSUMS) {
+                continue;
+            }
+
+            let to = dest.join(file.strip_prefix(&root)?);
+
+            fs::create_dir_all(to.parent().unwrap())?;
+            fs::copy(&file, to)?;
+        }
+
+        git2::Repository::init(&dest)?;
+
+        let repository = Self::open(&dest)?;
+        repository
+            .config
+            .commit_changes(&format!("Import '{}'", archive.display()))?;
+
+        Ok(repository)
+    }
+}
@@ -0,0 +1,209 @@
+use super::{
+    config::File,
+    escalate::{needs_root, Batch},
+};
+use crate::Dirs;
+use anyhow::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+};
+
+/// Everything a single import replaced, so it can be put back with `restore`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Backup {
+    pub id: String,
+    pub export: String,
+    pub date: String,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Entry {
+    pub file: File,
+    pub previous: Previous,
+}
+
+/// What was at a file's place before the import wrote to it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Previous {
+    Missing,
+    File,
+    Link(PathBuf),
+}
+
+impl Backup {
+    pub fn new(export: &str) -> Self {
+        let now = chrono::offset::Local::now();
+        let base = now.format("%Y%m%d-%H%M%S").to_string();
+
+        let mut id = base.clone();
+        let mut count = 1;
+
+        while Dirs::Backups.join(&id).exists() {
+            id = format!("{base}-{count}");
+            count += 1;
+        }
+
+        Self {
+            id,
+            export: export.into(),
+            date: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+            entries: vec![],
+        }
+    }
+
+    pub fn open(id: &str) -> Result<Self> {
+        let path = Dirs::Backups.join(id).join("manifest.yml");
+
+        if !path.exists() {
+            return Err(Error::msg(format!("Backup '{id}' not found")));
+        }
+
+        let contents = fs::read_to_string(path)?;
+
+        Ok(serde_yaml::from_str(&contents)?)
+    }
+
+    /// Every recorded run, oldest first
+    pub fn list() -> Result<Vec<Self>> {
+        let path = Dirs::Backups.path();
+
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut backups: Vec<_> = fs::read_dir(path)?
+            .filter_map(|entry| Self::open(&entry.ok()?.file_name().to_string_lossy()).ok())
+            .collect();
+
+        backups.sort_by(|a, b| a.id.cmp(&b.id));
+
+        Ok(backups)
+    }
+
+    pub fn latest() -> Result<Self> {
+        Self::list()?
+            .pop()
+            .ok_or_else(|| Error::msg("There are no backups to restore"))
+    }
+
+    pub fn path(&self) -> PathBuf {
+        Dirs::Backups.join(&self.id)
+    }
+
+    /// Where the previous contents of `file` are kept
+    pub fn stored(&self, file: &File) -> PathBuf {
+        self.path().join("files").join(file.to_string())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::create_dir_all(self.path())?;
+        fs::write(
+            self.path().join("manifest.yml"),
+            serde_yaml::to_string(self)?,
+        )?;
+
+        Ok(())
+    }
+
+    /// Drops whatever the recorded run backed up besides `entries`,
+    /// the whole run once none are left
+    pub fn prune(&self) -> Result<()> {
+        if self.entries.is_empty() {
+            fs::remove_dir_all(self.path())?;
+            return Ok(());
+        }
+
+        for entry in Self::open(&self.id)?.entries {
+            let stored = self.stored(&entry.file);
+
+            if !self.entries.contains(&entry) && stored.exists() {
+                fs::remove_file(stored)?;
+            }
+        }
+
+        self.save()
+    }
+
+    /// Puts back everything the run replaced and forgets about the run
+    ///
+    /// Root files the user can't write are put back through sudo or doas
+    pub fn restore(self, quiet: bool) -> Result<()> {
+        let mut elevated = Batch::default();
+
+        for entry in &self.entries {
+            let to = entry.file.stored_path();
+            let from = self.stored(&entry.file);
+
+            // the import may have failed before reaching this file
+            if entry.previous == Previous::File && !from.exists() {
+                continue;
+            }
+
+            if needs_root(&entry.file) {
+                elevated.remove(&to);
+
+                match &entry.previous {
+                    Previous::Missing => {}
+                    Previous::File => elevated.copy(&from, &to, None),
+                    Previous::Link(target) => elevated.link(target, &to),
+                }
+            } else {
+                restore_entry(entry, &from, &to)?;
+            }
+
+            if !quiet {
+                match entry.previous {
+                    Previous::Missing => println!("Removed '{}'", entry.file.user_path()),
+                    _ => println!("Restored '{}'", entry.file.user_path()),
+                }
+            }
+        }
+
+        elevated.run()?;
+
+        // a run that failed early may not have written its ledger yet
+        if self.path().exists() {
+            fs::remove_dir_all(self.path())?;
+        }
+
+        Ok(())
+    }
+}
+
+fn restore_entry(entry: &Entry, from: &Path, to: &Path) -> Result<()> {
+    if to.is_symlink() || to.is_file() {
+        fs::remove_file(to)?;
+    }
+
+    match &entry.previous {
+        Previous::Missing => {}
+        Previous::File => {
+            fs::create_dir_all(to.parent().unwrap())?;
+            fs::copy(from, to)?;
+        }
+        Previous::Link(target) => {
+            fs::create_dir_all(to.parent().unwrap())?;
+            symlink(target, to)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for Backup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}  {}  '{}' ({} files)",
+            self.id,
+            self.date,
+            self.export,
+            self.entries.len()
+        )
+    }
+}
@@ -1,12 +1,20 @@
-use crate::{Dirs, Expand};
-use anyhow::Result;
+use super::{
+    metadata::Metadata,
+    plan::{Action, Plan},
+    profile::Profile,
+    secret::leak_report,
+    template::Variables,
+};
+use crate::{path::resolve, Dirs, Expand};
+use anyhow::{Error, Result};
 use clap::crate_name;
+use git2::{IndexAddOption, Signature};
 use regex::Regex;
-use serde::{Deserialize, Serialize};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 use std::{
-    env::current_dir,
-    fs, io,
-    path::{Path, PathBuf}
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
 };
 use uuid::Uuid;
 
@@ -16,16 +24,80 @@ pub struct Config {
     pub name: String,
     pub files: Vec<File>,
 
+    /// Directories re-read on every save, so new files in them get picked up
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub directories: Vec<File>,
+
+    #[serde(default)]
+    pub deploy: Deploy,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// Files rendered with `variables` when imported instead of copied
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub templates: Vec<File>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: Variables,
+
+    /// Gitignore-style patterns left out when adding directories
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<String>,
+
+    /// Files stored encrypted inside `files/`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<File>,
+
+    /// age public keys secrets are encrypted to, a passphrase is used when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<String>,
+
+    /// Mode, and owner for root files, of every tracked file, keyed like `files/`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, Metadata>,
+
+    /// Whether modification times are saved and restored too
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mtimes: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<Author>,
+
     #[serde(skip_serializing, skip_deserializing)]
     pub path: PathBuf,
 }
 
+/// Who export commits are made by, instead of whoever git is configured with
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Author {
+    pub name: String,
+    pub email: String,
+}
+
+/// How `import` puts files in place when no flag overrides it
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Deploy {
+    #[default]
+    Copy,
+    Link,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum File {
     User(String),
     Root(String),
 }
 
+/// What `Config::save` did to a file inside `files/`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added(File),
+    Modified(File),
+    Removed(File),
+}
+
 impl Config {
     pub fn new(name: &str, dest: &Dirs) -> Result<Self> {
         let path = dest.join(name).join(format!("{}.yml", crate_name!()));
@@ -34,6 +106,17 @@ impl Config {
             id: Uuid::new_v4().to_string(),
             name: name.into(),
             files: vec![],
+            directories: vec![],
+            deploy: Deploy::default(),
+            profiles: BTreeMap::new(),
+            templates: vec![],
+            variables: Variables::new(),
+            ignore: vec![],
+            secrets: vec![],
+            recipients: vec![],
+            metadata: BTreeMap::new(),
+            mtimes: false,
+            author: None,
             path: path.clone(),
         };
 
@@ -58,52 +141,55 @@ impl Config {
         Ok(config)
     }
 
-    pub fn save(&mut self) -> Result<()> {
-        let files = Dirs::Files(self).path();
-
-        for file in self.missing_files() {
-            println!("Copying: {file:?}");
-
-            let dest = files.join(file.to_string());
-            fs::create_dir_all(dest.parent().unwrap())?;
-            fs::copy(file.stored_path(), &dest)?;
+    /// Syncs the live files into `files/`, writes the config
+    /// and commits, but only when something actually changed
+    ///
+    /// Commits are named after the current time unless a `message` is given
+    ///
+    /// Refuses when a file that isn't stored encrypted looks like it holds
+    /// a private key, token or password, unless `allow_secrets` is set
+    ///
+    /// Returns what was done, which is only printed when not `quiet`
+    pub fn save(
+        &mut self,
+        message: Option<&str>,
+        allow_secrets: bool,
+        quiet: bool,
+    ) -> Result<Plan> {
+        self.refresh()?;
+
+        let plan = self.plan_save(message)?;
+
+        if !allow_secrets {
+            let leaks = plan.leaks(self);
+
+            if !leaks.is_empty() {
+                return Err(Error::msg(leak_report(&leaks)));
+            }
         }
 
-        for file in self.lost_files() {
-            println!("Removing: {file:?}");
-
-            let path = files.join(file.to_string());
+        plan.apply(self, quiet)?;
 
-            fs::remove_file(path)?;
+        if !quiet
+            && !plan
+                .actions
+                .iter()
+                .any(|action| matches!(action, Action::Commit(_)))
+        {
+            println!("Everything up to date");
         }
 
-        remove_empty_dir_all(&files)?;
-
-        let f = fs::File::options()
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(&self.path)?;
-
-        serde_yaml::to_writer(f, &self)?;
-
-        self.commit_changes()?;
-
-        Ok(())
+        Ok(plan)
     }
 
-    fn missing_files(&self) -> Vec<&File> {
-        self.files
-            .iter()
-            .filter(|file| {
-                let path = Dirs::Files(self).join(file.to_string());
-
-                !path.exists()
-            })
-            .collect()
+    /// Re-reads what the config knows about the live files,
+    /// the tracked directories and every file's metadata
+    pub fn refresh(&mut self) -> Result<()> {
+        self.expand_directories()?;
+        self.record_metadata()
     }
 
-    fn lost_files(&self) -> Vec<File> {
+    pub(super) fn lost_files(&self) -> Vec<File> {
         let mut lost = vec![];
 
         let path = Dirs::Files(self).path();
@@ -111,9 +197,10 @@ impl Config {
         match path.expand().ok() {
             Some(found) => {
                 let found: Vec<_> = found.into_iter().map(File::from).collect();
+                let tracked = self.tracked();
 
                 for file in found {
-                    if !self.files.iter().any(|f| *f == file) {
+                    if !tracked.contains(&&file) {
                         lost.push(file);
                     }
                 }
@@ -124,45 +211,107 @@ impl Config {
         }
     }
 
-    pub fn append(&mut self, other: &mut Vec<File>) {
+    pub fn append(&mut self, other: &mut Vec<File>, profile: Option<&str>) {
+        let files = self.files_mut(profile);
+
         for file in other {
-            let repeated = self.files.iter().any(|f| f == file);
+            let repeated = files.iter().any(|f| f == file);
 
             if repeated {
                 continue;
             }
 
-            self.files.push(file.clone());
+            files.push(file.clone());
         }
     }
 
-    pub fn remove(&mut self, other: &mut Vec<File>) {
+    pub fn remove(&mut self, other: &mut Vec<File>, profile: Option<&str>) {
+        let files = self.files_mut(profile);
+
         for file in other {
-            let index = self.files.iter().position(|f| f == file);
+            let index = files.iter().position(|f| f == file);
 
             if let Some(index) = index {
-                self.files.swap_remove(index);
+                files.swap_remove(index);
+            }
+        }
+
+        self.forget_untracked();
+    }
+
+    /// Drops template and secret marks of files no longer tracked
+    pub(super) fn forget_untracked(&mut self) {
+        let tracked: Vec<_> = self.tracked().into_iter().cloned().collect();
+
+        self.templates.retain(|file| tracked.contains(file));
+        self.secrets.retain(|file| tracked.contains(file));
+    }
+
+    pub fn mark_templates(&mut self, files: &[File]) {
+        for file in files {
+            if !self.templates.contains(file) {
+                self.templates.push(file.clone());
             }
         }
     }
 
-    pub fn commit_changes(&self) -> Result<()> {
-        let repo_path = self.path.parent().unwrap();
+    pub fn mark_secrets(&mut self, files: &[File]) {
+        for file in files {
+            if !self.secrets.contains(file) {
+                self.secrets.push(file.clone());
+            }
+        }
+    }
+
+    /// Stages everything inside the export and commits it,
+    /// returning `false` when there was nothing to commit
+    pub fn commit_changes(&self, message: &str) -> Result<bool> {
+        let repository = git2::Repository::open(self.path.parent().unwrap())?;
+
+        let mut index = repository.index()?;
+        index.add_all(["*"], IndexAddOption::DEFAULT, None)?;
+        index.update_all(["*"], None)?;
+        index.write()?;
+
+        let tree = repository.find_tree(index.write_tree()?)?;
+        let parent = repository
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok());
+
+        if let Some(parent) = &parent {
+            if parent.tree_id() == tree.id() {
+                return Ok(false);
+            }
+        }
 
-        let message = format!("{}", chrono::offset::Utc::now().format("%Y-%m-%d %H:%M:%S"));
+        let signature = self.signature(&repository)?;
+        let parents: Vec<_> = parent.iter().collect();
 
-        // don't like this but works, who cares after all?
-        std::process::Command::new("git")
-            .args(["add", "-A"])
-            .current_dir(repo_path)
-            .output()?;
+        repository.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )?;
 
-        std::process::Command::new("git")
-            .args(["commit", "-m", &message])
-            .current_dir(repo_path)
-            .output()?;
+        Ok(true)
+    }
+
+    /// The `author` from the config, then the one from git's own config,
+    /// then a generic one so committing never depends on the machine
+    fn signature(&self, repository: &git2::Repository) -> Result<Signature<'static>> {
+        let signature = match &self.author {
+            Some(author) => Signature::now(&author.name, &author.email)?,
+            None => match repository.signature() {
+                Ok(signature) => signature.to_owned(),
+                Err(_) => Signature::now(crate_name!(), &format!("{}@localhost", crate_name!()))?,
+            },
+        };
 
-        Ok(())
+        Ok(signature)
     }
 }
 
@@ -219,17 +368,7 @@ impl TryFrom<&str> for File {
     type Error = anyhow::Error;
 
     fn try_from(value: &str) -> Result<Self> {
-        let mut path = PathBuf::from(value);
-
-        if !path.is_absolute() {
-            let current = current_dir()?;
-
-            path = match &value[..2] {
-                "~/" => Dirs::Home.join(&value[2..]),
-                "./" => current.join(&value[2..]),
-                _ => current.join(value),
-            };
-        }
+        let path = resolve(value)?;
 
         if path.exists() {
             Ok(Self::from(path))
@@ -247,36 +386,46 @@ impl TryFrom<&String> for File {
     }
 }
 
-impl ToString for File {
-    fn to_string(&self) -> String {
-        match self {
+impl fmt::Display for File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = match self {
             File::Root(ref file) => PathBuf::from("root").join(file),
             File::User(ref file) => PathBuf::from("user").join(file),
-        }
-        .display()
-        .to_string()
+        };
+
+        write!(f, "{}", path.display())
     }
 }
 
-fn remove_empty_dir_all<P: AsRef<Path>>(dir: P) -> Result<(), io::Error> {
-    remove_empty_dir_all_impl(dir.as_ref(), dir.as_ref())
+impl Change {
+    pub fn file(&self) -> &File {
+        match self {
+            Change::Added(file) | Change::Modified(file) | Change::Removed(file) => file,
+        }
+    }
 }
 
-fn remove_empty_dir_all_impl(dir: &Path, top: &Path) -> Result<(), io::Error> {
-    let entries: Vec<_> = fs::read_dir(dir)?.collect();
-
-    for entry in &entries {
-        let path = entry.as_ref().unwrap().path();
+impl Serialize for Change {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (change, file) = match self {
+            Change::Added(file) => ("added", file),
+            Change::Modified(file) => ("modified", file),
+            Change::Removed(file) => ("removed", file),
+        };
 
-        if path.is_dir() {
-            remove_empty_dir_all_impl(&path, top)?;
-        }
+        let mut state = serializer.serialize_struct("Change", 2)?;
+        state.serialize_field("change", change)?;
+        state.serialize_field("path", &file.user_path())?;
+        state.end()
     }
+}
 
-    if dir != top && entries.is_empty() {
-        fs::remove_dir(dir)?;
-        remove_empty_dir_all_impl(dir.parent().unwrap(), top)?;
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added(file) => write!(f, "Added '{}'", file.user_path()),
+            Change::Modified(file) => write!(f, "Updated '{}'", file.user_path()),
+            Change::Removed(file) => write!(f, "Removed '{}'", file.user_path()),
+        }
     }
-
-    Ok(())
 }
@@ -0,0 +1,207 @@
+use super::config::{Config, File};
+use crate::{Expand, Ignore};
+use anyhow::Result;
+use git2::{ObjectType, TreeWalkMode, TreeWalkResult};
+use std::path::{Path, PathBuf};
+
+/// The ref pointing at the commit the live files were last imported from
+const IMPORTED: &str = "refs/cup/imported";
+
+/// Whether `file` lives somewhere under the directory `dir`
+fn is_inside(file: &File, dir: &File) -> bool {
+    match (file, dir) {
+        (File::User(file), File::User(dir)) | (File::Root(file), File::Root(dir)) => {
+            Path::new(file).starts_with(dir)
+        }
+        _ => false,
+    }
+}
+
+/// Brings `files` in line with what is currently inside `directories`
+fn refresh(directories: &[File], files: &mut Vec<File>, ignore: &Ignore) -> Result<()> {
+    for dir in directories {
+        let path = dir.stored_path();
+
+        // a machine without the directory would otherwise empty it in the export
+        if !path.is_dir() {
+            continue;
+        }
+
+        let found: Vec<_> = path
+            .expand_ignoring(ignore)?
+            .into_iter()
+            .map(File::from)
+            .collect();
+
+        files.retain(|file| !is_inside(file, dir) || found.contains(file));
+
+        for file in found {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl Config {
+    /// The list of directories `add` and `remove` work on, creating the profile if needed
+    fn directories_mut(&mut self, profile: Option<&str>) -> &mut Vec<File> {
+        match profile {
+            Some(name) => &mut self.profiles.entry(name.into()).or_default().directories,
+            None => &mut self.directories,
+        }
+    }
+
+    pub fn mark_directories(&mut self, directories: &[File], profile: Option<&str>) {
+        let tracked = self.directories_mut(profile);
+
+        for dir in directories {
+            if !tracked.contains(dir) {
+                tracked.push(dir.clone());
+            }
+        }
+    }
+
+    /// Tracks every file in `paths` and inside the directories among them,
+    /// returning the files
+    pub fn add_paths(&mut self, paths: &[PathBuf], profile: Option<&str>) -> Result<Vec<File>> {
+        let ignore = Ignore::new(&self.ignore)?;
+
+        let mut files: Vec<_> = paths
+            .iter()
+            .filter_map(|p| p.expand_ignoring(&ignore).ok())
+            .flatten()
+            .map(File::from)
+            .collect();
+
+        let directories: Vec<_> = paths
+            .iter()
+            .filter(|p| p.is_dir())
+            .cloned()
+            .map(File::from)
+            .collect();
+
+        self.append(&mut files, profile);
+        self.mark_directories(&directories, profile);
+
+        Ok(files)
+    }
+
+    /// Stops tracking `directories` along with every file inside them
+    pub fn remove_directories(&mut self, directories: &[File], profile: Option<&str>) {
+        self.directories_mut(profile)
+            .retain(|dir| !directories.contains(dir));
+
+        self.files_mut(profile)
+            .retain(|file| !directories.iter().any(|dir| is_inside(file, dir)));
+
+        self.forget_untracked();
+    }
+
+    /// Re-reads every tracked directory, adding the files created
+    /// in them and dropping the ones deleted since the last save
+    pub fn expand_directories(&mut self) -> Result<()> {
+        let ignore = Ignore::new(&self.ignore)?;
+
+        refresh(&self.directories, &mut self.files, &ignore)?;
+
+        for profile in self.profiles.values_mut() {
+            refresh(&profile.directories, &mut profile.files, &ignore)?;
+        }
+
+        self.forget_untracked();
+
+        Ok(())
+    }
+
+    /// The shared directories plus the ones from `profiles`
+    pub fn selected_directories(&self, profiles: &[String]) -> Vec<&File> {
+        let mut directories: Vec<_> = self.directories.iter().collect();
+
+        for profile in profiles.iter().filter_map(|name| self.profiles.get(name)) {
+            for dir in &profile.directories {
+                if !directories.contains(&dir) {
+                    directories.push(dir);
+                }
+            }
+        }
+
+        directories
+    }
+
+    /// Live files inside the selected directories that the export had when
+    /// it was last imported and has dropped since
+    ///
+    /// Anything else in those directories was never the export's to delete,
+    /// so nothing is stale before the first import
+    pub fn stale_files(&self, profiles: &[String]) -> Result<Vec<File>> {
+        let ignore = Ignore::new(&self.ignore)?;
+        let tracked = self.tracked();
+        let directories = self.selected_directories(profiles);
+        let mut stale = vec![];
+
+        for file in self.imported_files()? {
+            let path = file.stored_path();
+
+            let dropped = !tracked.contains(&&file)
+                && directories.iter().any(|dir| is_inside(&file, dir))
+                && !ignore.is_ignored(&path, false);
+
+            if dropped && path.symlink_metadata().is_ok() && !stale.contains(&file) {
+                stale.push(file);
+            }
+        }
+
+        Ok(stale)
+    }
+
+    /// Remembers the commit the live files now come from, for `stale_files`
+    pub fn mark_imported(&self) -> Result<()> {
+        let repository = git2::Repository::open(self.path.parent().unwrap())?;
+
+        // an export without commits has nothing to remember
+        let Ok(head) = repository.head() else {
+            return Ok(());
+        };
+
+        repository.reference(IMPORTED, head.peel_to_commit()?.id(), true, "import")?;
+
+        Ok(())
+    }
+
+    /// The files inside `files/` as of the last import
+    fn imported_files(&self) -> Result<Vec<File>> {
+        let repository = git2::Repository::open(self.path.parent().unwrap())?;
+
+        let Ok(reference) = repository.find_reference(IMPORTED) else {
+            return Ok(vec![]);
+        };
+
+        let tree = reference.peel_to_tree()?;
+        let Ok(entry) = tree.get_path(Path::new("files")) else {
+            return Ok(vec![]);
+        };
+
+        let mut files = vec![];
+
+        repository
+            .find_tree(entry.id())?
+            .walk(TreeWalkMode::PreOrder, |dir, entry| {
+                if entry.kind() == Some(ObjectType::Blob) {
+                    let path = Path::new(dir).join(entry.name().unwrap_or_default());
+
+                    if let Ok(file) = path.strip_prefix("user") {
+                        files.push(File::User(file.display().to_string()));
+                    } else if let Ok(file) = path.strip_prefix("root") {
+                        files.push(File::Root(file.display().to_string()));
+                    }
+                }
+
+                TreeWalkResult::Ok
+            })?;
+
+        Ok(files)
+    }
+}
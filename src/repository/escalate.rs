@@ -0,0 +1,222 @@
+use super::{
+    config::{Config, File},
+    metadata::Metadata,
+    plan::{staging_path, Action, Plan},
+};
+use crate::Dirs;
+use anyhow::{Error, Result};
+use chrono::TimeZone;
+use std::{
+    env,
+    ffi::CString,
+    fs,
+    io::Write,
+    os::unix::{ffi::OsStrExt, fs::OpenOptionsExt},
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Whether this process may replace `path`, or create it if it's missing
+fn writable(path: &Path) -> bool {
+    let access = |path: &Path| {
+        CString::new(path.as_os_str().as_bytes())
+            .is_ok_and(|path| unsafe { libc::access(path.as_ptr(), libc::W_OK) } == 0)
+    };
+
+    if path.exists() && !access(path) {
+        return false;
+    }
+
+    // links and deletions need the directory to be writable as well
+    path.ancestors()
+        .skip(1)
+        .find(|dir| dir.exists())
+        .is_some_and(access)
+}
+
+pub(super) fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Root files this process can't write on its own
+pub fn needs_root(file: &File) -> bool {
+    matches!(file, File::Root(_)) && !is_root() && !writable(&file.stored_path())
+}
+
+/// `CUP_ESCALATE`, otherwise whichever of sudo and doas is installed
+pub fn escalation() -> Result<String> {
+    if let Ok(program) = env::var("CUP_ESCALATE") {
+        return Ok(program);
+    }
+
+    let path = env::var_os("PATH").unwrap_or_default();
+
+    ["sudo", "doas"]
+        .into_iter()
+        .find(|program| env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+        .map(String::from)
+        .ok_or_else(|| {
+            Error::msg("Neither sudo nor doas were found, set CUP_ESCALATE or run cup as root")
+        })
+}
+
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Root writes put aside while a plan is applied, so they all
+/// happen in one elevated shell and only one password is asked for
+#[derive(Debug, Default)]
+pub struct Batch {
+    script: Vec<String>,
+    staged: Vec<PathBuf>,
+}
+
+impl Batch {
+    /// Adds `command` with every path quoted after it
+    fn line(&mut self, command: &str, paths: &[&Path]) {
+        let mut line = command.to_string();
+
+        for path in paths {
+            line.push(' ');
+            line.push_str(&quote(&path.display().to_string()));
+        }
+
+        self.script.push(line);
+    }
+
+    /// Writes `contents` somewhere only the current user can read until the batch runs
+    fn stage(&mut self, contents: &[u8]) -> Result<PathBuf> {
+        let dir = Dirs::Cache.join("staged");
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join(format!("{}-{}", process::id(), self.staged.len()));
+
+        fs::File::options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&path)
+            .and_then(|mut f| f.write_all(contents))?;
+
+        self.staged.push(path.clone());
+
+        Ok(path)
+    }
+
+    fn metadata(&mut self, metadata: &Metadata, path: &Path) {
+        if let (Some(uid), Some(gid)) = (metadata.uid, metadata.gid) {
+            self.line(&format!("chown {uid}:{gid}"), &[path]);
+        }
+
+        if let Some(mtime) = metadata.mtime {
+            if let Some(date) = chrono::Local.timestamp_opt(mtime as i64, 0).single() {
+                self.line(
+                    &format!("touch -t {}", date.format("%Y%m%d%H%M.%S")),
+                    &[path],
+                );
+            }
+        }
+
+        self.line(&format!("chmod {:o}", metadata.mode), &[path]);
+    }
+
+    /// Copies `from` beside `to` and renames it over, like unprivileged writes
+    pub fn copy(&mut self, from: &Path, to: &Path, metadata: Option<&Metadata>) {
+        let staged = staging_path(to);
+
+        self.line("mkdir -p", &[to.parent().unwrap()]);
+        self.line("cp", &[from, &staged]);
+
+        if let Some(metadata) = metadata {
+            self.metadata(metadata, &staged);
+        }
+
+        self.line("mv -f", &[&staged, to]);
+    }
+
+    pub fn link(&mut self, target: &Path, to: &Path) {
+        let staged = staging_path(to);
+
+        self.line("mkdir -p", &[to.parent().unwrap()]);
+        self.line("ln -sf", &[target, &staged]);
+        self.line("mv -f", &[&staged, to]);
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.line("rm -f", &[path]);
+    }
+
+    pub fn push(&mut self, action: &Action, config: &Config) -> Result<()> {
+        let stored = |file: &File| Dirs::Files(config).join(file.to_string());
+        let metadata = |file: &File| config.metadata.get(&file.to_string());
+
+        match action {
+            Action::Backup { file, to } => {
+                // the copy stays in the user's backup store, owned by them
+                fs::create_dir_all(to.parent().unwrap())?;
+
+                let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+
+                self.line("cp", &[&file.stored_path(), to]);
+                self.line(&format!("chown {uid}:{gid}"), &[to]);
+            }
+            Action::Unlink(file) | Action::Delete(file) => self.remove(&file.stored_path()),
+            Action::Copy(file) => self.copy(&stored(file), &file.stored_path(), metadata(file)),
+            Action::Link(file) => self.link(&stored(file), &file.stored_path()),
+            Action::Write { file, contents } => {
+                let staged = self.stage(contents)?;
+
+                self.copy(&staged, &file.stored_path(), metadata(file));
+            }
+            _ => return Err(Error::msg(format!("'{action}' can't be run as root"))),
+        }
+
+        Ok(())
+    }
+
+    /// Runs everything gathered through sudo or doas, stopping at the first failure
+    pub fn run(self) -> Result<()> {
+        if self.script.is_empty() {
+            return Ok(());
+        }
+
+        let script = format!("set -e\n{}", self.script.join("\n"));
+
+        let status = process::Command::new(escalation()?)
+            .args(["sh", "-c", &script])
+            .status()?;
+
+        if !status.success() {
+            return Err(Error::msg("Writing the root files failed"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Staged contents may be decrypted secrets, they never outlive the batch
+/// even when it never got to run
+impl Drop for Batch {
+    fn drop(&mut self) {
+        for staged in &self.staged {
+            fs::remove_file(staged).ok();
+        }
+    }
+}
+
+impl Plan {
+    /// Root files the plan writes that need sudo or doas
+    pub fn root_files(&self) -> Vec<&File> {
+        let mut files = vec![];
+
+        for file in self.actions.iter().filter_map(Action::file) {
+            if !files.contains(&file) && needs_root(file) {
+                files.push(file);
+            }
+        }
+
+        files
+    }
+}
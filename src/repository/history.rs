@@ -0,0 +1,144 @@
+use super::{
+    config::{Change, File},
+    Config, Repository,
+};
+use anyhow::{Error, Result};
+use chrono::{TimeZone, Utc};
+use git2::{build::CheckoutBuilder, Delta, Oid, Sort, StatusOptions};
+use serde::Serialize;
+use std::{fmt, path::Path};
+
+/// A commit of the export along with the files it touched
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Revision {
+    pub id: String,
+    pub date: String,
+    pub message: String,
+    pub changes: Vec<Change>,
+}
+
+impl Repository {
+    /// Every commit reachable from `HEAD`, newest first
+    pub fn log(&self) -> Result<Vec<Revision>> {
+        let mut walk = self.repository.revwalk()?;
+
+        if walk.push_head().is_err() {
+            return Ok(vec![]);
+        }
+
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+
+        walk.map(|id| self.revision(id?)).collect()
+    }
+
+    /// The commit `HEAD` points to, nothing for an export without commits
+    pub fn last_commit(&self) -> Result<Option<Revision>> {
+        match self.repository.head() {
+            Ok(head) => Ok(Some(self.revision(head.peel_to_commit()?.id())?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Brings the export back to how it was at `rev`, recording it as a new commit
+    /// so the history stays pushable
+    ///
+    /// Refuses while the export has uncommitted changes, the checkout
+    /// would throw them away
+    pub fn checkout(&mut self, rev: &str) -> Result<Revision> {
+        let commit = self.repository.revparse_single(rev)?.peel_to_commit()?;
+        let id = commit.id().to_string()[..7].to_string();
+
+        let statuses = self.repository.statuses(Some(
+            StatusOptions::new()
+                .include_untracked(true)
+                .recurse_untracked_dirs(true),
+        ))?;
+
+        if !statuses.is_empty() {
+            let paths: Vec<_> = statuses
+                .iter()
+                .filter_map(|entry| entry.path().map(String::from))
+                .collect();
+
+            return Err(Error::msg(format!(
+                "'{}' has uncommitted changes, save or discard them first: {}",
+                self.config.name,
+                paths.join(", ")
+            )));
+        }
+
+        self.repository.checkout_tree(
+            commit.as_object(),
+            Some(CheckoutBuilder::new().force().remove_untracked(true)),
+        )?;
+
+        self.config = Config::open(&self.path)?;
+        self.config.commit_changes(&format!("Rollback to {id}"))?;
+
+        let head = self.repository.head()?.peel_to_commit()?;
+
+        self.revision(head.id())
+    }
+
+    fn revision(&self, id: Oid) -> Result<Revision> {
+        let commit = self.repository.find_commit(id)?;
+
+        let tree = commit.tree()?;
+        let parent = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree()?),
+            Err(_) => None,
+        };
+
+        let diff = self
+            .repository
+            .diff_tree_to_tree(parent.as_ref(), Some(&tree), None)?;
+
+        let changes = diff
+            .deltas()
+            .filter_map(|delta| {
+                let path = delta.new_file().path().or(delta.old_file().path())?;
+
+                if !path.starts_with("files") {
+                    return None;
+                }
+
+                let file = self.file(path);
+
+                match delta.status() {
+                    Delta::Added => Some(Change::Added(file)),
+                    Delta::Deleted => Some(Change::Removed(file)),
+                    _ => Some(Change::Modified(file)),
+                }
+            })
+            .collect();
+
+        let date = Utc
+            .timestamp_opt(commit.time().seconds(), 0)
+            .single()
+            .unwrap_or_default();
+
+        Ok(Revision {
+            id: id.to_string()[..7].to_string(),
+            date: date.format("%Y-%m-%d %H:%M:%S").to_string(),
+            message: commit.summary().unwrap_or_default().to_string(),
+            changes,
+        })
+    }
+
+    /// Maps a path relative to the repository, like `files/user/.bashrc`, to its entry
+    fn file(&self, path: &Path) -> File {
+        File::from(self.path.join(path))
+    }
+}
+
+impl fmt::Display for Revision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}  {}  {}", self.id, self.date, self.message)?;
+
+        for change in &self.changes {
+            write!(f, "\n    {change}")?;
+        }
+
+        Ok(())
+    }
+}
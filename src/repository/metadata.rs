@@ -0,0 +1,107 @@
+use super::{
+    config::{Config, File},
+    escalate::is_root,
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    os::unix::fs::{chown, MetadataExt, PermissionsExt},
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+/// What git doesn't keep about a file, recorded in `cup.yml` on save
+/// and put back on import
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Metadata {
+    #[serde(with = "octal")]
+    pub mode: u32,
+
+    /// Only kept for root files, user files belong to whoever imports them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+
+    /// Seconds since the epoch, only kept when the export has `mtimes` set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<u64>,
+}
+
+/// Modes read as `0644` in `cup.yml` rather than as a decimal number
+mod octal {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(mode: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{mode:04o}"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        let mode = String::deserialize(deserializer)?;
+
+        u32::from_str_radix(&mode, 8).map_err(D::Error::custom)
+    }
+}
+
+impl Config {
+    /// Reads the metadata of every tracked file, keeping what was known
+    /// about the ones missing on this machine
+    pub fn record_metadata(&mut self) -> Result<()> {
+        let mut metadata = BTreeMap::new();
+
+        for file in self.tracked() {
+            let key = file.to_string();
+            let path = file.stored_path();
+
+            if !path.exists() {
+                if let Some(known) = self.metadata.get(&key) {
+                    metadata.insert(key, known.clone());
+                }
+                continue;
+            }
+
+            let live = fs::metadata(path)?;
+            let is_root = matches!(file, File::Root(_));
+
+            metadata.insert(
+                key,
+                Metadata {
+                    mode: live.permissions().mode() & 0o7777,
+                    uid: is_root.then(|| live.uid()),
+                    gid: is_root.then(|| live.gid()),
+                    mtime: match self.mtimes {
+                        true => Some(live.mtime().max(0) as u64),
+                        false => None,
+                    },
+                },
+            );
+        }
+
+        self.metadata = metadata;
+
+        Ok(())
+    }
+
+    /// Gives `path`, written for `file`, the owner, mode and mtime `file` was saved with
+    pub fn apply_metadata(&self, file: &File, path: &Path) -> Result<()> {
+        let Some(metadata) = self.metadata.get(&file.to_string()) else {
+            return Ok(());
+        };
+
+        // chown clears setuid bits, so it goes before the mode, only root
+        // may give files away and files that need root get chowned by the batch
+        if is_root() && (metadata.uid.is_some() || metadata.gid.is_some()) {
+            chown(path, metadata.uid, metadata.gid)?;
+        }
+
+        if let Some(mtime) = metadata.mtime {
+            fs::File::open(path)?.set_modified(UNIX_EPOCH + Duration::from_secs(mtime))?;
+        }
+
+        fs::set_permissions(path, fs::Permissions::from_mode(metadata.mode))?;
+
+        Ok(())
+    }
+}
@@ -1,10 +1,28 @@
+mod archive;
+pub mod backup;
 pub mod config;
+mod directory;
+mod escalate;
+pub mod history;
+mod metadata;
+pub mod plan;
+pub mod profile;
+mod remote;
+mod secret;
+mod source;
+mod status;
+pub mod template;
+
+pub use backup::Backup;
 pub use config::Config;
+pub use plan::Plan;
+pub use secret::leak_report;
+pub use status::FileStatus;
 
 use crate::Dirs;
 use anyhow::{Error, Result};
-use git2::{self, build::RepoBuilder, Cred, FetchOptions, RemoteCallbacks};
-use regex::Regex;
+use git2::{self, build::RepoBuilder, Cred, CredentialType, FetchOptions, RemoteCallbacks};
+use source::Source;
 use std::{
     fs, io,
     path::{Path, PathBuf},
@@ -18,68 +36,69 @@ pub struct Repository {
 }
 
 impl Repository {
-    /// ## Possible Inputs
+    /// Clones `source` into `dest`, see `Source::parse` for what it accepts
     ///
-    /// * `<username>/<repository>`
-    ///
-    /// * `git@<ssh>:<username>/<repository>`
-    ///
-    /// * `<protocol>://<website>/<username>/<repository>`
-    pub fn clone<P: AsRef<Path>>(url: &str, dest: P) -> Result<Self> {
-        // https://regexpattern.com/git-repository
-        // Fields: ([protocol   secure?   website]   OR   [git])   username   repository
-        let regex = Regex::new(&format!(
-            r"^(?:(?:{}|{})(?::(?://{}/)?))?{}/{}(?:\.git)?$",
-            r"(?P<protocol>git|http(?P<secure>s)?)",
-            r"(?P<ssh>git@[\w\d\.:]+)",
-            r"(?P<website>[\w\d\.\[\]:_-]+?)",
-            r"(?P<username>[\w\d-]+)",
-            r"(?P<repository>[\w\d\._-]+?)",
-        ))?;
-
-        if !regex.is_match(url) {
-            return Err(Error::msg("Invalid Repository"));
+    /// An export already at `dest` is fast-forwarded instead of cloned over
+    pub fn clone<P: AsRef<Path>>(source: &str, dest: P) -> Result<Self> {
+        match Source::parse(source)? {
+            Source::Git { url, name } => Self::clone_url(&url, &dest.as_ref().join(name)),
+            Source::Archive(path) => Self::unpack(&path, dest.as_ref()),
         }
-        let captures = regex.captures(url).unwrap();
-
-        let user = captures.name("username").unwrap().as_str();
-        let repo = captures.name("repository").unwrap().as_str();
-
-        let dest = dest.as_ref().join(repo);
+    }
 
+    fn clone_url(url: &str, dest: &Path) -> Result<Self> {
         if dest.exists() {
-            fs::remove_dir_all(&dest)?;
+            return Self::update(url, dest);
         }
 
-        let make_repository = |repository| -> Result<Self> {
-            let config = Config::open(&dest)?;
+        let mut fetch_options = FetchOptions::new();
+        let mut builder = RepoBuilder::new();
 
-            Ok(Self {
-                repository,
-                path: dest.clone(),
-                config,
-            })
-        };
+        fetch_options.remote_callbacks(remote_callbacks());
+        builder.fetch_options(fetch_options);
 
-        if captures.name("ssh").is_some() || captures.name("website").is_some() {
-            git2::Repository::clone(url, &dest).map(make_repository)?
-        } else {
-            let url = format!("git@github.com:{user}/{repo}");
-            let key = get_ssh_key()?;
+        builder.clone(url, dest)?;
 
-            let mut callbacks = RemoteCallbacks::new();
-            let mut fetch_options = FetchOptions::new();
-            let mut builder = RepoBuilder::new();
+        // a clone that isn't an export would block the next import
+        let result = Self::open(dest);
 
-            callbacks.credentials(|_, username, _| {
-                Cred::ssh_key(username.unwrap(), None, Path::new(&key), None)
-            });
+        if result.is_err() {
+            fs::remove_dir_all(dest).ok();
+        }
 
-            fetch_options.remote_callbacks(callbacks);
-            builder.fetch_options(fetch_options);
+        result
+    }
 
-            builder.clone(&url, &dest).map(make_repository)?
-        }
+    /// Pulls `url` into the export at `dest`, which keeps its unpushed commits,
+    /// refusing when it comes from somewhere else or has diverged
+    fn update(url: &str, dest: &Path) -> Result<Self> {
+        let mut repository = Self::open(dest).map_err(|_| {
+            Error::msg(format!(
+                "'{}' already exists and is not an export",
+                dest.display()
+            ))
+        })?;
+
+        let remote = repository
+            .remotes()?
+            .into_iter()
+            .find(|(_, remote)| remote == url)
+            .map(|(name, _)| name)
+            .ok_or_else(|| {
+                Error::msg(format!(
+                    "'{}' already exists but was not imported from '{url}', delete it or add the url as a remote",
+                    dest.display()
+                ))
+            })?;
+
+        repository.pull(&remote).map_err(|err| {
+            Error::msg(format!(
+                "{err}, push or drop the local commits of '{}' first",
+                repository.config.name
+            ))
+        })?;
+
+        Ok(repository)
     }
 
     pub fn init(name: &str, dest: &Dirs) -> Result<Self> {
@@ -126,6 +145,23 @@ impl std::fmt::Debug for Repository {
     }
 }
 
+/// Authenticates with the first key found in `~/.ssh` whenever the remote asks for one
+fn remote_callbacks<'a>() -> RemoteCallbacks<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+
+    callbacks.credentials(|_, username, allowed| {
+        if allowed.contains(CredentialType::SSH_KEY) {
+            let key = get_ssh_key().map_err(|err| git2::Error::from_str(&err.to_string()))?;
+
+            Cred::ssh_key(username.unwrap_or("git"), None, Path::new(&key), None)
+        } else {
+            Cred::default()
+        }
+    });
+
+    callbacks
+}
+
 fn get_ssh_key() -> Result<String> {
     let ssh_dir = Dirs::Home.join(".ssh");
 
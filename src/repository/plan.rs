@@ -0,0 +1,727 @@
+use super::{
+    backup::{Backup, Entry, Previous},
+    config::{Change, Config, Deploy, File},
+    escalate::{escalation, Batch},
+    template::UndefinedVariables,
+};
+use crate::Dirs;
+use anyhow::{Error, Result};
+use serde::{Serialize, Serializer};
+use std::{
+    fmt, fs, io,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Everything `save` or `import` is about to do, computed up front
+/// so it can be printed instead of applied
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Plan {
+    pub actions: Vec<Action>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Copies a live file into `files/` or deletes an untracked copy from it
+    Save(Change),
+    /// Removes a directory inside `files/` left empty by removals
+    RemoveDir(PathBuf),
+    WriteConfig,
+    Commit(String),
+    /// Writes the manifest of an import run into the backup store
+    Record(Backup),
+    /// Copies an existing live file aside before it gets replaced
+    Backup {
+        file: File,
+        to: PathBuf,
+    },
+    /// Removes a live symlink so nothing gets written through it
+    Unlink(File),
+    /// Removes a live file its tracked directory no longer has
+    Delete(File),
+    /// Drops the backups of an import run with `--overwrite` once it succeeded,
+    /// all but the entries it still holds
+    Discard(Backup),
+    Copy(File),
+    Link(File),
+    /// Writes contents cup produced itself, like rendered templates or decrypted secrets
+    Write {
+        file: File,
+        contents: Vec<u8>,
+    },
+    Skip {
+        file: File,
+        reason: &'static str,
+    },
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    pub fn changes(&self) -> Vec<Change> {
+        self.actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::Save(change) => Some(change.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Applies every action in order, except for root files the user can't
+    /// write, which are done together at the end through sudo or doas
+    ///
+    /// Live files are written beside their target first and only renamed
+    /// over it once all of them were written, if anything fails after that
+    /// whatever the run replaced is put back from its backup
+    pub fn apply(&self, config: &Config, quiet: bool) -> Result<()> {
+        let root = self.root_files();
+
+        // better to fail before anything was touched
+        if !root.is_empty() {
+            let program = escalation()?;
+
+            if !quiet {
+                println!("These files need root and will be written with {program}:");
+
+                for file in &root {
+                    println!("  {}", file.user_path());
+                }
+            }
+        }
+
+        for action in &self.actions {
+            if action.file().is_some_and(|file| root.contains(&file)) {
+                continue;
+            }
+
+            if let Err(err) = action.stage(config) {
+                self.discard_staged();
+                return Err(err);
+            }
+        }
+
+        let mut touched = vec![];
+
+        if let Err(err) = self.apply_staged(config, &root, quiet, &mut touched) {
+            self.discard_staged();
+            return Err(self.roll_back(err, &touched, quiet));
+        }
+
+        // backups are only dropped once nothing can need them for a roll back,
+        // root ones are copied by the elevated batch so not before it ran
+        for action in &self.actions {
+            if let Action::Discard(_) = action {
+                if !quiet {
+                    println!("{action}");
+                }
+
+                action.apply(config)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies everything but `Discard`, adding each live file it changed to
+    /// `touched` so a roll back leaves alone whatever it never got to
+    fn apply_staged<'a>(
+        &'a self,
+        config: &Config,
+        root: &[&'a File],
+        quiet: bool,
+        touched: &mut Vec<&'a File>,
+    ) -> Result<()> {
+        let mut elevated = Batch::default();
+
+        for action in &self.actions {
+            if let Action::Discard(_) = action {
+                continue;
+            }
+
+            if !quiet {
+                println!("{action}");
+            }
+
+            match action.file() {
+                Some(file) if root.contains(&file) => elevated.push(action, config)?,
+                file => {
+                    action.apply(config)?;
+
+                    // a backup only reads the live file
+                    if let (Some(file), false) = (file, matches!(action, Action::Backup { .. })) {
+                        touched.push(file);
+                    }
+                }
+            }
+        }
+
+        // the batch stops at its first failure, but where isn't known
+        touched.extend(root);
+
+        elevated.run()
+    }
+
+    /// Removes whatever staged files are still lying around
+    fn discard_staged(&self) {
+        for action in &self.actions {
+            if let Action::Copy(file) | Action::Link(file) | Action::Write { file, .. } = action {
+                let staged = staging_path(&file.stored_path());
+
+                if staged.symlink_metadata().is_ok() {
+                    fs::remove_file(staged).ok();
+                }
+            }
+        }
+    }
+
+    /// Puts back the `touched` files the run replaced before `err` stopped it
+    fn roll_back(&self, err: Error, touched: &[&File], quiet: bool) -> Error {
+        let Some(recorded) = self.actions.iter().find_map(|action| match action {
+            Action::Record(backup) => Some(backup),
+            _ => None,
+        }) else {
+            return err;
+        };
+
+        let backup = Backup {
+            entries: recorded
+                .entries
+                .iter()
+                .filter(|entry| touched.contains(&&entry.file))
+                .cloned()
+                .collect(),
+            ..recorded.clone()
+        };
+
+        if !quiet {
+            println!("Rolling back '{}'", backup.id);
+        }
+
+        // a later `restore` of the run shouldn't reach further than this one
+        if backup.path().exists() {
+            if let Err(save) = backup.save() {
+                return err.context(format!("Import failed and so did rolling it back ({save})"));
+            }
+        }
+
+        match backup.restore(quiet) {
+            Ok(()) => err.context("Import failed, every replaced file was put back"),
+            Err(restore) => err.context(format!(
+                "Import failed and so did rolling it back ({restore}), run `restore {}`",
+                recorded.id
+            )),
+        }
+    }
+}
+
+/// Where a live file is written before being renamed over `to`,
+/// next to it so the rename never crosses filesystems
+pub(super) fn staging_path(to: &Path) -> PathBuf {
+    let name = to.file_name().unwrap_or_default().to_string_lossy();
+
+    to.with_file_name(format!(".{name}.cup-{}", process::id()))
+}
+
+impl Action {
+    /// The live file the action writes to, if any
+    pub fn file(&self) -> Option<&File> {
+        match self {
+            Action::Backup { file, .. }
+            | Action::Unlink(file)
+            | Action::Delete(file)
+            | Action::Copy(file)
+            | Action::Link(file)
+            | Action::Write { file, .. } => Some(file),
+            _ => None,
+        }
+    }
+
+    /// Writes what `Copy`, `Link` and `Write` put in place to its staging path
+    fn stage(&self, config: &Config) -> Result<()> {
+        let stored = |file: &File| Dirs::Files(config).join(file.to_string());
+
+        let file = match self {
+            Action::Copy(file) | Action::Link(file) | Action::Write { file, .. } => file,
+            _ => return Ok(()),
+        };
+
+        let staged = staging_path(&file.stored_path());
+
+        fs::create_dir_all(staged.parent().unwrap())?;
+
+        match self {
+            Action::Copy(file) => {
+                fs::copy(stored(file), &staged)?;
+                config.apply_metadata(file, &staged)?;
+            }
+            Action::Link(file) => symlink(stored(file), &staged)?,
+            Action::Write { file, contents } => {
+                fs::write(&staged, contents)?;
+                config.apply_metadata(file, &staged)?;
+            }
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    fn apply(&self, config: &Config) -> Result<()> {
+        let stored = |file: &File| Dirs::Files(config).join(file.to_string());
+
+        match self {
+            Action::Save(Change::Added(file) | Change::Modified(file)) => {
+                let dest = stored(file);
+
+                fs::create_dir_all(dest.parent().unwrap())?;
+
+                if config.is_secret(file) {
+                    fs::write(dest, config.encrypt(&fs::read(file.stored_path())?)?)?;
+                } else {
+                    fs::copy(file.stored_path(), dest)?;
+                }
+            }
+            Action::Save(Change::Removed(file)) => fs::remove_file(stored(file))?,
+            Action::RemoveDir(dir) => fs::remove_dir(dir)?,
+            Action::WriteConfig => fs::write(&config.path, serde_yaml::to_string(config)?)?,
+            Action::Commit(message) => {
+                config.commit_changes(message)?;
+            }
+            Action::Record(backup) => backup.save()?,
+            Action::Backup { file, to } => {
+                fs::create_dir_all(to.parent().unwrap())?;
+                fs::copy(file.stored_path(), to)?;
+            }
+            Action::Unlink(file) | Action::Delete(file) => fs::remove_file(file.stored_path())?,
+            Action::Discard(backup) => backup.prune()?,
+            Action::Copy(file) | Action::Link(file) | Action::Write { file, .. } => {
+                let to = file.stored_path();
+
+                fs::rename(staging_path(&to), to)?;
+            }
+            Action::Skip { .. } => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl Config {
+    /// Works out what `save` has to do to bring `files/` and `cup.yml` up to date
+    pub fn plan_save(&self, message: Option<&str>) -> Result<Plan> {
+        let files = Dirs::Files(self).path();
+        let mut actions = vec![];
+
+        for file in self.tracked() {
+            let from = file.stored_path();
+            let dest = files.join(file.to_string());
+
+            if !from.exists() {
+                actions.push(Action::Skip {
+                    file: file.clone(),
+                    reason: "missing locally",
+                });
+                continue;
+            }
+
+            if !dest.exists() {
+                actions.push(Action::Save(Change::Added(file.clone())));
+            } else if !self.is_template(file) && !self.in_sync(file, &[])? {
+                actions.push(Action::Save(Change::Modified(file.clone())));
+            }
+        }
+
+        let lost = self.lost_files();
+        let removed: Vec<_> = lost
+            .iter()
+            .map(|file| files.join(file.to_string()))
+            .collect();
+
+        actions.extend(
+            lost.into_iter()
+                .map(|file| Action::Save(Change::Removed(file))),
+        );
+
+        let added: Vec<_> = actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::Save(Change::Added(file)) => Some(files.join(file.to_string())),
+                _ => None,
+            })
+            .collect();
+
+        if files.exists() {
+            actions.extend(
+                empty_dirs(&files, &removed, &added)?
+                    .into_iter()
+                    .map(Action::RemoveDir),
+            );
+        }
+
+        let contents = serde_yaml::to_string(self)?;
+        let config_changed = fs::read_to_string(&self.path).ok().as_ref() != Some(&contents);
+
+        if config_changed {
+            actions.push(Action::WriteConfig);
+        }
+
+        if !self.edited_templates()?.is_empty()
+            || actions
+                .iter()
+                .any(|action| matches!(action, Action::Save(_) | Action::WriteConfig))
+        {
+            let message = match message {
+                Some(message) => message.into(),
+                None => chrono::offset::Utc::now()
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string(),
+            };
+
+            actions.push(Action::Commit(message));
+        }
+
+        Ok(Plan { actions })
+    }
+
+    /// Works out what `import` has to do to put every tracked file in place,
+    /// recording whatever it replaces so `restore` can undo it
+    pub fn plan_import(
+        &self,
+        profiles: &[String],
+        deploy: Deploy,
+        overwrite: bool,
+    ) -> Result<Plan> {
+        let mut backup = Backup::new(&self.name);
+        let mut actions = vec![];
+
+        for file in self.selected(profiles)? {
+            let from = Dirs::Files(self).join(file.to_string());
+            let to = file.stored_path();
+
+            let is_link = to.is_symlink();
+
+            // generated files differ from what is stored, so they can never be links
+            let deploy = match self.is_generated(file) {
+                true => None,
+                false => Some(deploy),
+            };
+
+            let contents = match deploy {
+                Some(_) => None,
+                None => match self.deployed_contents(file, profiles) {
+                    Ok(contents) => Some(contents),
+                    // the rest of the export can still be imported
+                    Err(err) if err.is::<UndefinedVariables>() => {
+                        actions.push(Action::Skip {
+                            file: file.clone(),
+                            reason: "its template uses undefined variables",
+                        });
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                },
+            };
+
+            if is_link && deploy == Some(Deploy::Link) && fs::read_link(&to)? == from {
+                actions.push(Action::Skip {
+                    file: file.clone(),
+                    reason: "already linked",
+                });
+                continue;
+            }
+
+            let previous = match (is_link, to.exists()) {
+                (true, _) => Previous::Link(fs::read_link(&to)?),
+                (false, true) => Previous::File,
+                (false, false) => Previous::Missing,
+            };
+
+            // even with `overwrite` a copy is kept until the import went through
+            if previous == Previous::File {
+                actions.push(Action::Backup {
+                    file: file.clone(),
+                    to: backup.stored(file),
+                });
+            }
+
+            backup.entries.push(Entry {
+                file: file.clone(),
+                previous,
+            });
+
+            if is_link {
+                actions.push(Action::Unlink(file.clone()));
+            }
+
+            actions.push(match (contents, deploy) {
+                (Some(contents), _) => Action::Write {
+                    file: file.clone(),
+                    contents,
+                },
+                (None, Some(Deploy::Link)) => Action::Link(file.clone()),
+                (None, _) => Action::Copy(file.clone()),
+            });
+        }
+
+        let mut deleted = vec![];
+
+        // whatever was deleted from a tracked directory goes away here too
+        for file in self.stale_files(profiles)? {
+            let to = file.stored_path();
+
+            let previous = match to.is_symlink() {
+                true => Previous::Link(fs::read_link(&to)?),
+                false => Previous::File,
+            };
+
+            if previous == Previous::File {
+                actions.push(Action::Backup {
+                    file: file.clone(),
+                    to: backup.stored(&file),
+                });
+            }
+
+            let entry = Entry {
+                file: file.clone(),
+                previous,
+            };
+
+            deleted.push(entry.clone());
+            backup.entries.push(entry);
+            actions.push(Action::Delete(file));
+        }
+
+        // the ledger goes first so even a failed import can be restored
+        if !backup.entries.is_empty() {
+            // deleted files have no other copy outside the export's history,
+            // so even `overwrite` keeps them restorable
+            if overwrite && deleted.len() < backup.entries.len() {
+                actions.push(Action::Discard(Backup {
+                    entries: deleted,
+                    ..backup.clone()
+                }));
+            }
+
+            actions.insert(0, Action::Record(backup));
+        }
+
+        Ok(Plan { actions })
+    }
+}
+
+/// Directories under `top` that will be empty once `removed` files are gone
+/// and that none of the `added` files go into, deepest first so they can
+/// be removed in order
+fn empty_dirs(top: &Path, removed: &[PathBuf], added: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut dirs = vec![];
+
+    empty_dirs_impl(top, removed, &mut dirs)?;
+    dirs.retain(|dir| dir != top && !added.iter().any(|file| file.starts_with(dir)));
+
+    Ok(dirs)
+}
+
+fn empty_dirs_impl(dir: &Path, removed: &[PathBuf], dirs: &mut Vec<PathBuf>) -> io::Result<bool> {
+    let mut empty = true;
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        let gone = match path.is_dir() && !path.is_symlink() {
+            true => empty_dirs_impl(&path, removed, dirs)?,
+            false => removed.contains(&path),
+        };
+
+        empty &= gone;
+    }
+
+    if empty {
+        dirs.push(dir.into());
+    }
+
+    Ok(empty)
+}
+
+/// An action the way `--format` prints it
+#[derive(Serialize)]
+struct Step {
+    action: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+impl Step {
+    fn new(action: &'static str, path: Option<String>, detail: Option<String>) -> Self {
+        Self {
+            action,
+            path,
+            detail,
+        }
+    }
+}
+
+impl Serialize for Action {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let path = |file: &File| Some(file.user_path());
+
+        let step = match self {
+            Action::Save(Change::Added(file)) => Step::new("add", path(file), None),
+            Action::Save(Change::Modified(file)) => Step::new("update", path(file), None),
+            Action::Save(Change::Removed(file)) => Step::new("remove", path(file), None),
+            Action::RemoveDir(dir) => {
+                Step::new("remove_dir", Some(dir.display().to_string()), None)
+            }
+            Action::WriteConfig => Step::new("write_config", None, None),
+            Action::Commit(message) => Step::new("commit", None, Some(message.clone())),
+            Action::Record(backup) => Step::new("record", None, Some(backup.id.clone())),
+            Action::Backup { file, to } => {
+                Step::new("backup", path(file), Some(to.display().to_string()))
+            }
+            Action::Unlink(file) => Step::new("unlink", path(file), None),
+            Action::Delete(file) => Step::new("delete", path(file), None),
+            Action::Discard(backup) => Step::new("discard", None, Some(backup.id.clone())),
+            Action::Copy(file) => Step::new("copy", path(file), None),
+            Action::Link(file) => Step::new("link", path(file), None),
+            Action::Write { file, .. } => Step::new("write", path(file), None),
+            Action::Skip { file, reason } => {
+                Step::new("skip", path(file), Some(reason.to_string()))
+            }
+        };
+
+        step.serialize(serializer)
+    }
+}
+
+impl Serialize for Plan {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(&self.actions)
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "Nothing to do");
+        }
+
+        let lines: Vec<_> = self.actions.iter().map(Action::to_string).collect();
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Save(Change::Added(file)) => write!(f, "Add '{}'", file.user_path()),
+            Action::Save(Change::Modified(file)) => write!(f, "Update '{}'", file.user_path()),
+            Action::Save(Change::Removed(file)) => write!(f, "Remove '{}'", file.user_path()),
+            Action::RemoveDir(dir) => write!(f, "Remove empty directory '{}'", dir.display()),
+            Action::WriteConfig => write!(f, "Write config"),
+            Action::Commit(message) => write!(f, "Commit '{message}'"),
+            Action::Record(backup) => write!(f, "Record backup '{}'", backup.id),
+            Action::Backup { file, to } => {
+                write!(f, "Back up '{}' to '{}'", file.user_path(), to.display())
+            }
+            Action::Unlink(file) => write!(f, "Unlink '{}'", file.user_path()),
+            Action::Delete(file) => write!(f, "Delete '{}'", file.user_path()),
+            Action::Discard(backup) if backup.entries.is_empty() => {
+                write!(f, "Discard backup '{}'", backup.id)
+            }
+            Action::Discard(backup) => {
+                write!(f, "Discard backup '{}' except for deleted files", backup.id)
+            }
+            Action::Copy(file) => write!(f, "Copy '{}'", file.user_path()),
+            Action::Link(file) => write!(f, "Link '{}'", file.user_path()),
+            Action::Write { file, .. } => write!(f, "Write '{}'", file.user_path()),
+            Action::Skip { file, reason } => {
+                write!(f, "Skip '{}', {reason}", file.user_path())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Root files are absolute, so they can live in a scratch directory
+    fn file(path: &Path) -> File {
+        File::Root(path.strip_prefix("/").unwrap().display().to_string())
+    }
+
+    #[test]
+    fn failed_import_rolls_back_only_what_it_reached() {
+        let dir = std::env::temp_dir().join(format!("cup-plan-{}", process::id()));
+
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(dir.join("export")).unwrap();
+        fs::create_dir_all(dir.join("live")).unwrap();
+
+        // keeps the backups out of the real state directory
+        std::env::set_var("XDG_STATE_HOME", dir.join("state"));
+
+        fs::write(
+            dir.join("export/cup.yml"),
+            "id: test\nname: test\nfiles: []\n",
+        )
+        .unwrap();
+
+        let config = Config::open(dir.join("export")).unwrap();
+
+        let replaced = file(&dir.join("live/replaced"));
+        let missing = file(&dir.join("live/missing"));
+        let unreached = file(&dir.join("live/unreached"));
+
+        let stored = Dirs::Files(&config).join(replaced.to_string());
+        fs::create_dir_all(stored.parent().unwrap()).unwrap();
+        fs::write(stored, "imported").unwrap();
+
+        fs::write(replaced.stored_path(), "local").unwrap();
+        // showed up after the plan was made, the run never gets to it
+        fs::write(unreached.stored_path(), "local").unwrap();
+
+        let mut backup = Backup::new("test");
+
+        for (file, previous) in [
+            (&replaced, Previous::File),
+            (&missing, Previous::File),
+            (&unreached, Previous::Missing),
+        ] {
+            backup.entries.push(Entry {
+                file: file.clone(),
+                previous,
+            });
+        }
+
+        let plan = Plan {
+            actions: vec![
+                Action::Record(backup.clone()),
+                Action::Backup {
+                    file: replaced.clone(),
+                    to: backup.stored(&replaced),
+                },
+                Action::Copy(replaced.clone()),
+                // fails, there is nothing to delete
+                Action::Delete(missing.clone()),
+                Action::Link(unreached.clone()),
+            ],
+        };
+
+        assert!(plan.apply(&config, true).is_err());
+
+        assert_eq!(fs::read_to_string(replaced.stored_path()).unwrap(), "local");
+        assert_eq!(
+            fs::read_to_string(unreached.stored_path()).unwrap(),
+            "local"
+        );
+        assert!(!backup.path().exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
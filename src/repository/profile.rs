@@ -0,0 +1,91 @@
+use super::{
+    config::{Config, File},
+    template::Variables,
+};
+use anyhow::{Error, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// A set of files that only applies to some machines
+///
+/// `hosts` accepts `*` wildcards, e.g. `build-*`
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Profile {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<String>,
+    #[serde(default)]
+    pub files: Vec<File>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub directories: Vec<File>,
+    #[serde(default, skip_serializing_if = "Variables::is_empty")]
+    pub variables: Variables,
+}
+
+impl Profile {
+    pub fn matches(&self, hostname: &str) -> bool {
+        self.hosts.iter().any(|host| {
+            let pattern = format!("^{}$", regex::escape(host).replace(r"\*", ".*"));
+
+            Regex::new(&pattern).is_ok_and(|regex| regex.is_match(hostname))
+        })
+    }
+}
+
+impl Config {
+    /// Every file of the export, whatever profile it belongs to
+    pub fn tracked(&self) -> Vec<&File> {
+        let mut files: Vec<_> = self.files.iter().collect();
+
+        for file in self.profiles.values().flat_map(|profile| &profile.files) {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+
+        files
+    }
+
+    /// The shared files plus the ones from `profiles`
+    pub fn selected(&self, profiles: &[String]) -> Result<Vec<&File>> {
+        let mut files: Vec<_> = self.files.iter().collect();
+
+        for name in profiles {
+            let profile = self
+                .profiles
+                .get(name)
+                .ok_or_else(|| Error::msg(format!("Profile '{name}' does not exist")))?;
+
+            for file in &profile.files {
+                if !files.contains(&file) {
+                    files.push(file);
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Profiles whose hosts match this machine's hostname
+    pub fn host_profiles(&self) -> Vec<String> {
+        let hostname = whoami::fallible::hostname().unwrap_or_default();
+
+        self.profiles
+            .iter()
+            .filter(|(_, profile)| profile.matches(&hostname))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Files of the host's profiles, what `status` and `diff` look at
+    pub fn host_files(&self) -> Vec<&File> {
+        self.selected(&self.host_profiles()).unwrap_or_default()
+    }
+
+    /// The list `add` and `remove` work on, creating the profile if needed
+    pub fn files_mut(&mut self, profile: Option<&str>) -> &mut Vec<File> {
+        match profile {
+            Some(name) => &mut self.profiles.entry(name.into()).or_default().files,
+            None => &mut self.files,
+        }
+    }
+}
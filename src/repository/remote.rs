@@ -0,0 +1,252 @@
+use super::{remote_callbacks, Config, Repository};
+use anyhow::{Error, Result};
+use git2::{build::CheckoutBuilder, FetchOptions, Oid, PushOptions, Remote};
+use std::path::Path;
+
+impl Repository {
+    /// Every configured remote as `(name, url)`
+    pub fn remotes(&self) -> Result<Vec<(String, String)>> {
+        let names = self.repository.remotes()?;
+
+        let remotes = names
+            .iter()
+            .flatten()
+            .map(|name| {
+                let remote = self.repository.find_remote(name)?;
+                let url = remote.url().unwrap_or_default().to_string();
+
+                Ok((name.to_string(), url))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(remotes)
+    }
+
+    pub fn add_remote(&self, name: &str, url: &str) -> Result<()> {
+        self.repository.remote(name, url)?;
+
+        Ok(())
+    }
+
+    pub fn remove_remote(&self, name: &str) -> Result<()> {
+        self.repository.remote_delete(name)?;
+
+        Ok(())
+    }
+
+    /// Pushes the current branch to the branch with the same name on `remote`
+    pub fn push(&self, remote: &str) -> Result<()> {
+        let branch = self.branch()?;
+        let refspec = format!("refs/heads/{branch}:refs/heads/{branch}");
+
+        let mut callbacks = remote_callbacks();
+        callbacks.push_update_reference(|reference, status| match status {
+            Some(message) => Err(git2::Error::from_str(&format!(
+                "'{reference}' was rejected: {message}"
+            ))),
+            None => Ok(()),
+        });
+
+        let mut options = PushOptions::new();
+        options.remote_callbacks(callbacks);
+
+        let mut remote = self.repository.find_remote(remote)?;
+        remote.push(&[&refspec], Some(&mut options))?;
+
+        Ok(())
+    }
+
+    /// Fetches `remote` and fast-forwards the current branch,
+    /// refusing when the histories have diverged
+    ///
+    /// Returns whether anything changed
+    pub fn pull(&mut self, remote: &str) -> Result<bool> {
+        let fetched = self.fetch(&mut self.repository.find_remote(remote)?, remote)?;
+
+        self.fast_forward(fetched, remote)
+    }
+
+    /// Like `pull`, from a repository on disk that isn't a remote of the export
+    pub fn pull_path(&mut self, path: &Path, name: &str) -> Result<bool> {
+        let url = path.display().to_string();
+        let fetched = self.fetch(&mut self.repository.remote_anonymous(&url)?, name)?;
+
+        self.fast_forward(fetched, name)
+    }
+
+    /// Fetches the current branch into `refs/remotes/<name>/`, returning where it points
+    fn fetch(&self, remote: &mut Remote, name: &str) -> Result<Oid> {
+        let branch = self.branch()?;
+
+        let mut options = FetchOptions::new();
+        options.remote_callbacks(remote_callbacks());
+
+        let tracking = format!("refs/remotes/{name}/{branch}");
+        let refspec = format!("+refs/heads/{branch}:{tracking}");
+
+        remote.fetch(&[&refspec], Some(&mut options), None)?;
+
+        let fetched = self
+            .repository
+            .find_reference(&tracking)
+            .map_err(|_| Error::msg(format!("'{name}' has no branch '{branch}'")))?;
+
+        fetched
+            .target()
+            .ok_or_else(|| Error::msg(format!("'{tracking}' is not a direct reference")))
+    }
+
+    fn fast_forward(&mut self, fetched: Oid, remote: &str) -> Result<bool> {
+        let branch = self.branch()?;
+        let fetched = self.repository.find_annotated_commit(fetched)?;
+
+        let (analysis, _) = self.repository.merge_analysis(&[&fetched])?;
+
+        if analysis.is_up_to_date() {
+            return Ok(false);
+        }
+
+        if !analysis.is_fast_forward() {
+            return Err(Error::msg(format!(
+                "'{remote}/{branch}' has diverged from the local export, it can't be fast-forwarded"
+            )));
+        }
+
+        let name = format!("refs/heads/{branch}");
+        let mut reference = self.repository.find_reference(&name)?;
+
+        // a safe checkout bails out instead of clobbering uncommitted files
+        self.repository.checkout_tree(
+            &self.repository.find_object(fetched.id(), None)?,
+            Some(CheckoutBuilder::new().safe()),
+        )?;
+        reference.set_target(fetched.id(), "cup: fast-forward pull")?;
+        self.repository.set_head(&name)?;
+
+        self.config = Config::open(&self.path)?;
+
+        Ok(true)
+    }
+
+    fn branch(&self) -> Result<String> {
+        let head = self
+            .repository
+            .head()
+            .map_err(|_| Error::msg("The export has no commits yet"))?;
+
+        Ok(head.shorthand().unwrap_or("master").to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::PathBuf, process};
+
+    /// A scratch directory for one test, emptied first
+    fn scratch(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cup-remote-{}-{test}", process::id()));
+
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    /// A new export at `path` with one commit
+    fn export(path: &Path) -> Repository {
+        git2::Repository::init(path).unwrap();
+        fs::write(
+            path.join("cup.yml"),
+            "id: test\nname: test\nfiles: []\nauthor:\n  name: test\n  email: test@localhost\n",
+        )
+        .unwrap();
+
+        let repository = Repository::open(path).unwrap();
+        repository.config.commit_changes("Create").unwrap();
+
+        repository
+    }
+
+    fn commit(repository: &Repository, name: &str, contents: &str) {
+        let files = repository.path.join("files");
+
+        fs::create_dir_all(&files).unwrap();
+        fs::write(files.join(name), contents).unwrap();
+
+        repository.config.commit_changes(name).unwrap();
+    }
+
+    /// A bare remote `origin` of `repository` holding what it has committed so far,
+    /// and a clone of it at `path`
+    fn share(repository: &Repository, dir: &Path, path: &Path) -> Repository {
+        let bare = dir.join("remote.git");
+        let branch = repository.branch().unwrap();
+
+        git2::Repository::init_bare(&bare)
+            .unwrap()
+            .set_head(&format!("refs/heads/{branch}"))
+            .unwrap();
+
+        repository
+            .add_remote("origin", &bare.display().to_string())
+            .unwrap();
+        repository.push("origin").unwrap();
+
+        git2::Repository::clone(&bare.display().to_string(), path).unwrap();
+
+        Repository::open(path).unwrap()
+    }
+
+    #[test]
+    fn pull_gets_what_was_pushed() {
+        let dir = scratch("pull");
+        let local = export(&dir.join("local"));
+        let mut other = share(&local, &dir, &dir.join("other"));
+
+        commit(&local, "a", "pushed");
+        local.push("origin").unwrap();
+
+        assert!(other.pull("origin").unwrap());
+        assert_eq!(
+            fs::read_to_string(dir.join("other/files/a")).unwrap(),
+            "pushed"
+        );
+        assert!(!other.pull("origin").unwrap());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn pull_refuses_diverged_history() {
+        let dir = scratch("diverged");
+        let local = export(&dir.join("local"));
+        let mut other = share(&local, &dir, &dir.join("other"));
+
+        commit(&local, "a", "pushed");
+        local.push("origin").unwrap();
+        commit(&other, "b", "local only");
+
+        let err = other.pull("origin").unwrap_err();
+
+        assert!(err.to_string().contains("diverged"));
+        assert!(!dir.join("other/files/a").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn push_refuses_diverged_history() {
+        let dir = scratch("rejected");
+        let local = export(&dir.join("local"));
+        let other = share(&local, &dir, &dir.join("other"));
+
+        commit(&local, "a", "pushed first");
+        local.push("origin").unwrap();
+        commit(&other, "b", "pushed second");
+
+        assert!(other.push("origin").is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,212 @@
+use super::{
+    config::{Change, Config, File},
+    plan::{Action, Plan},
+};
+use crate::{
+    path::leak::{find_leaks, Leak},
+    Dirs,
+};
+use age::{scrypt, secrecy::SecretString, x25519, Decryptor, Encryptor, IdentityFile, Recipient};
+use anyhow::{Error, Result};
+use dialoguer::Password;
+use std::{
+    env, fs,
+    io::{self, IsTerminal, Read, Write},
+    iter,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Asked once per run, `CUP_PASSPHRASE` skips the prompt
+fn passphrase() -> Result<SecretString> {
+    static PASSPHRASE: OnceLock<String> = OnceLock::new();
+
+    if let Some(passphrase) = PASSPHRASE.get() {
+        return Ok(passphrase.clone().into());
+    }
+
+    let passphrase = match env::var("CUP_PASSPHRASE") {
+        Ok(passphrase) => passphrase,
+        Err(_) if !io::stdin().is_terminal() => {
+            return Err(Error::msg(
+                "Secret files need a passphrase, set CUP_PASSPHRASE",
+            ))
+        }
+        Err(_) => Password::new()
+            .with_prompt("Passphrase for secret files")
+            .interact()?,
+    };
+
+    Ok(PASSPHRASE.get_or_init(|| passphrase).clone().into())
+}
+
+/// How age files start, binary or armored
+const HEADERS: [&[u8]; 2] = [
+    b"age-encryption.org/",
+    b"-----BEGIN AGE ENCRYPTED FILE-----",
+];
+
+fn has_age_header(contents: &[u8]) -> bool {
+    HEADERS.iter().any(|header| contents.starts_with(header))
+}
+
+/// Whether `path` holds an age file
+pub(super) fn is_encrypted(path: &Path) -> Result<bool> {
+    let mut head = vec![];
+    fs::File::open(path)?
+        .take(HEADERS[1].len() as u64)
+        .read_to_end(&mut head)?;
+
+    Ok(has_age_header(&head))
+}
+
+/// `CUP_IDENTITY` or `identity.txt` in cup's config directory
+fn identity_file() -> PathBuf {
+    match env::var("CUP_IDENTITY") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => Dirs::Config.join("identity.txt"),
+    }
+}
+
+impl Config {
+    pub fn is_secret(&self, file: &File) -> bool {
+        self.secrets.contains(file)
+    }
+
+    /// Which of `files` some commit of the export holds unencrypted,
+    /// encrypting them now doesn't take them out of the history
+    pub fn committed_in_plaintext(&self, files: &[File]) -> Result<Vec<File>> {
+        let repository = git2::Repository::open(self.path.parent().unwrap())?;
+        let mut walk = repository.revwalk()?;
+
+        if walk.push_head().is_err() {
+            return Ok(vec![]);
+        }
+
+        let mut found = vec![];
+
+        for id in walk {
+            let tree = repository.find_commit(id?)?.tree()?;
+
+            for file in files {
+                if found.contains(file) {
+                    continue;
+                }
+
+                let Ok(entry) = tree.get_path(&Path::new("files").join(file.to_string())) else {
+                    continue;
+                };
+
+                if let Ok(blob) = entry.to_object(&repository)?.into_blob() {
+                    if !has_age_header(blob.content()) {
+                        found.push(file.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// Encrypts to the export's `recipients` when it has any, with a passphrase otherwise
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let encryptor = match self.recipients.is_empty() {
+            true => Encryptor::with_user_passphrase(passphrase()?),
+            false => {
+                let recipients = self
+                    .recipients
+                    .iter()
+                    .map(|key| key.parse::<x25519::Recipient>().map_err(Error::msg))
+                    .collect::<Result<Vec<_>>>()?;
+
+                Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn Recipient))?
+            }
+        };
+
+        let mut encrypted = vec![];
+        let mut writer = encryptor.wrap_output(&mut encrypted)?;
+
+        writer.write_all(plaintext)?;
+        writer.finish()?;
+
+        Ok(encrypted)
+    }
+
+    pub fn decrypt(&self, encrypted: &[u8]) -> Result<Vec<u8>> {
+        let decryptor = Decryptor::new(encrypted)?;
+
+        let mut reader = match decryptor.is_scrypt() {
+            true => {
+                let identity = scrypt::Identity::new(passphrase()?);
+
+                decryptor.decrypt(iter::once(&identity as _))?
+            }
+            false => {
+                let path = identity_file();
+
+                let identities = IdentityFile::from_file(path.display().to_string())
+                    .map_err(|err| {
+                        Error::msg(format!("Can't read identity '{}': {err}", path.display()))
+                    })?
+                    .into_identities()?;
+
+                decryptor.decrypt(identities.iter().map(|i| i.as_ref() as _))?
+            }
+        };
+
+        let mut decrypted = vec![];
+        reader.read_to_end(&mut decrypted)?;
+
+        Ok(decrypted)
+    }
+}
+
+impl Plan {
+    /// Lines that look like secrets in the files the plan saves unencrypted
+    pub fn leaks(&self, config: &Config) -> Vec<(File, Leak)> {
+        let mut leaks = vec![];
+
+        for action in &self.actions {
+            let Action::Save(Change::Added(file) | Change::Modified(file)) = action else {
+                continue;
+            };
+
+            if config.is_secret(file) {
+                continue;
+            }
+
+            // unreadable files make the save itself fail with a better error
+            let Ok(contents) = fs::read(file.stored_path()) else {
+                continue;
+            };
+
+            leaks.extend(
+                find_leaks(&contents)
+                    .into_iter()
+                    .map(|leak| (file.clone(), leak)),
+            );
+        }
+
+        leaks
+    }
+}
+
+/// What `save` prints when it refuses to commit `leaks`
+pub fn leak_report(leaks: &[(File, Leak)]) -> String {
+    let mut report = String::from("These files look like they hold secrets:");
+
+    for (file, leak) in leaks {
+        report.push_str(&format!(
+            "\n  {}:{}  {}",
+            file.user_path(),
+            leak.line,
+            leak.kind
+        ));
+    }
+
+    report.push_str(
+        "\nStore them encrypted with `export add --secret`, or pass --allow-secrets to commit them as they are",
+    );
+
+    report
+}
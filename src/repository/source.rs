@@ -0,0 +1,99 @@
+use crate::path::resolve;
+use anyhow::{Error, Result};
+use regex::Regex;
+use std::path::PathBuf;
+
+/// Where `import` reads an export from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// Anything git can clone, remote urls as well as local repositories
+    Git { url: String, name: String },
+    /// A `.tar.gz` export bundle
+    Archive(PathBuf),
+}
+
+impl Source {
+    /// ## Possible Inputs
+    ///
+    /// * `<username>/<repository>`
+    ///
+    /// * `git@<ssh>:<username>/<repository>`
+    ///
+    /// * `<protocol>://<website>/<username>/<repository>`
+    ///
+    /// * `file://<path>` or a plain path, to a checkout or a bare repository
+    ///
+    /// * the path of a `.tar.gz` bundle
+    pub fn parse(input: &str) -> Result<Self> {
+        let path = match input.strip_prefix("file://") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => resolve(input).ok().filter(|path| path.exists()),
+        };
+
+        let looks_local = ["/", "./", "../", "~"].iter().any(|p| input.starts_with(p))
+            || input.ends_with(".tar.gz")
+            || input.ends_with(".tgz");
+
+        match path {
+            Some(path) => Self::local(path),
+            None if looks_local => Err(Error::msg(format!("'{input}' does not exist"))),
+            None => Self::remote(input),
+        }
+    }
+
+    fn local(path: PathBuf) -> Result<Self> {
+        let display = path.display().to_string();
+
+        if path.is_file() && (display.ends_with(".tar.gz") || display.ends_with(".tgz")) {
+            return Ok(Self::Archive(path));
+        }
+
+        if !path.is_dir() {
+            return Err(Error::msg(format!(
+                "'{display}' is neither a repository nor a .tar.gz bundle"
+            )));
+        }
+
+        let path = path.canonicalize()?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+
+        Ok(Self::Git {
+            name: name.strip_suffix(".git").unwrap_or(&name).to_string(),
+            url: path.display().to_string(),
+        })
+    }
+
+    fn remote(url: &str) -> Result<Self> {
+        // https://regexpattern.com/git-repository
+        // Fields: ([protocol   secure?   website]   OR   [git])   username   repository
+        let regex = Regex::new(&format!(
+            r"^(?:(?:{}|{})(?::(?://{}/)?))?{}/{}(?:\.git)?$",
+            r"(?P<protocol>git|http(?P<secure>s)?)",
+            r"(?P<ssh>git@[\w\d\.:]+)",
+            r"(?P<website>[\w\d\.\[\]:_-]+?)",
+            r"(?P<username>[\w\d-]+)",
+            r"(?P<repository>[\w\d\._-]+?)",
+        ))?;
+
+        if !regex.is_match(url) {
+            return Err(Error::msg("Invalid Repository"));
+        }
+        let captures = regex.captures(url).unwrap();
+
+        let user = captures.name("username").unwrap().as_str();
+        let repo = captures.name("repository").unwrap().as_str();
+
+        let url = match captures.name("ssh").is_some() || captures.name("website").is_some() {
+            true => url.to_string(),
+            false => format!("git@github.com:{user}/{repo}"),
+        };
+
+        Ok(Self::Git {
+            url,
+            name: repo.into(),
+        })
+    }
+}
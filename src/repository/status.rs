@@ -0,0 +1,106 @@
+use super::{
+    config::{Config, File},
+    template::UndefinedVariables,
+};
+use crate::{path::compare::same_contents, Dirs};
+use anyhow::Result;
+use serde::Serialize;
+use std::{fmt, fs};
+
+/// How a tracked file compares between the live system and `files/`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Unchanged,
+    ModifiedLocally,
+    ModifiedInExport,
+    MissingLocally,
+    MissingInExport,
+    /// A template that can't be rendered on this machine, so there's
+    /// nothing to compare the live file with
+    Unrenderable,
+}
+
+impl Config {
+    /// Compares every tracked file with its copy inside the export
+    ///
+    /// When both copies exist but differ, the most recently modified one
+    /// is assumed to hold the newer edit
+    pub fn status(&self) -> Result<Vec<(File, FileStatus)>> {
+        let files = Dirs::Files(self).path();
+        let profiles = self.host_profiles();
+
+        self.host_files()
+            .into_iter()
+            .map(|file| {
+                let live = file.stored_path();
+                let stored = files.join(file.to_string());
+
+                let status = match (live.exists(), stored.exists()) {
+                    (false, _) => FileStatus::MissingLocally,
+                    (true, false) => FileStatus::MissingInExport,
+                    (true, true) => match self.in_sync(file, &profiles) {
+                        Ok(true) => FileStatus::Unchanged,
+                        Ok(false) => {
+                            let live = fs::metadata(&live)?.modified()?;
+                            let stored = fs::metadata(&stored)?.modified()?;
+
+                            if live >= stored {
+                                FileStatus::ModifiedLocally
+                            } else {
+                                FileStatus::ModifiedInExport
+                            }
+                        }
+                        // one broken template shouldn't hide how the rest compare
+                        Err(err) if err.is::<UndefinedVariables>() => FileStatus::Unrenderable,
+                        Err(err) => return Err(err),
+                    },
+                };
+
+                Ok((file.clone(), status))
+            })
+            .collect()
+    }
+}
+
+impl Config {
+    /// How many of the plain files tracked on this host differ from
+    /// their copy in the export, cheap enough to run on every export
+    ///
+    /// Secrets and templates are left out, comparing them means
+    /// decrypting or rendering
+    pub fn drift(&self) -> Result<usize> {
+        let files = Dirs::Files(self).path();
+        let mut drift = 0;
+
+        for file in self.host_files() {
+            if self.is_generated(file) {
+                continue;
+            }
+
+            let live = file.stored_path();
+            let stored = files.join(file.to_string());
+
+            if !live.exists() || !stored.exists() || !same_contents(live, stored)? {
+                drift += 1;
+            }
+        }
+
+        Ok(drift)
+    }
+}
+
+impl fmt::Display for FileStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            FileStatus::Unchanged => "unchanged",
+            FileStatus::ModifiedLocally => "modified locally",
+            FileStatus::ModifiedInExport => "modified in export",
+            FileStatus::MissingLocally => "missing locally",
+            FileStatus::MissingInExport => "missing in export",
+            FileStatus::Unrenderable => "undefined variables",
+        };
+
+        f.pad(status)
+    }
+}
@@ -0,0 +1,164 @@
+use super::{
+    config::{Config, File},
+    secret::is_encrypted,
+};
+use crate::{path::compare::same_contents, Dirs};
+use anyhow::{Error, Result};
+use regex::{Captures, Regex};
+use std::{collections::BTreeMap, env, fmt, fs};
+
+pub type Variables = BTreeMap<String, String>;
+
+/// Names a template uses that no variable defines, kept apart from other
+/// errors so one broken template doesn't stop commands working on the rest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndefinedVariables(pub Vec<String>);
+
+impl fmt::Display for UndefinedVariables {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Undefined template variables: {}", self.0.join(", "))
+    }
+}
+
+impl std::error::Error for UndefinedVariables {}
+
+/// Replaces every `{{ name }}` in `template`, failing on unknown names
+/// so a half rendered file never gets deployed
+pub fn render(template: &str, variables: &Variables) -> Result<String> {
+    let regex = Regex::new(r"\{\{\s*([\w.-]+)\s*\}\}")?;
+    let mut missing = vec![];
+
+    let rendered = regex.replace_all(template, |captures: &Captures| {
+        let name = &captures[1];
+
+        match variables.get(name) {
+            Some(value) => value.clone(),
+            None => {
+                missing.push(name.to_string());
+                String::new()
+            }
+        }
+    });
+
+    if !missing.is_empty() {
+        return Err(UndefinedVariables(missing).into());
+    }
+
+    Ok(rendered.into_owned())
+}
+
+/// Variables describing the machine cup runs on
+fn host_variables() -> Variables {
+    let mut variables = Variables::new();
+
+    variables.insert(
+        "hostname".into(),
+        whoami::fallible::hostname().unwrap_or_default(),
+    );
+    variables.insert("user".into(), whoami::username());
+    variables.insert("os".into(), env::consts::OS.into());
+    variables.insert("arch".into(), env::consts::ARCH.into());
+    variables.insert("home".into(), Dirs::Home.to_string());
+
+    variables
+}
+
+impl Config {
+    pub fn is_template(&self, file: &File) -> bool {
+        self.templates.contains(file)
+    }
+
+    /// Templates whose source in `files/` was edited since the last commit,
+    /// templates are never copied over from the live system so only their
+    /// source changes
+    pub fn edited_templates(&self) -> Result<Vec<File>> {
+        let root = self.path.parent().unwrap();
+        let repository = git2::Repository::open(root)?;
+        let files = Dirs::Files(self).path();
+
+        let mut edited = vec![];
+
+        for file in &self.templates {
+            let stored = files.join(file.to_string());
+
+            let Ok(relative) = stored.strip_prefix(root) else {
+                continue;
+            };
+
+            if !stored.exists() {
+                continue;
+            }
+
+            if repository.status_file(relative)? != git2::Status::CURRENT {
+                edited.push(file.clone());
+            }
+        }
+
+        Ok(edited)
+    }
+
+    /// Host variables, overridden by the export's, then the profiles',
+    /// then the ones in the local `variables.yml`
+    pub fn variables(&self, profiles: &[String]) -> Result<Variables> {
+        let mut variables = host_variables();
+
+        variables.extend(self.variables.clone());
+
+        for name in profiles {
+            if let Some(profile) = self.profiles.get(name) {
+                variables.extend(profile.variables.clone());
+            }
+        }
+
+        let overrides = Dirs::Config.join("variables.yml");
+
+        if overrides.exists() {
+            let contents = fs::read_to_string(overrides)?;
+            let overrides: Variables = serde_yaml::from_str(&contents)?;
+
+            variables.extend(overrides);
+        }
+
+        Ok(variables)
+    }
+
+    /// Files `import` writes itself rather than copying or linking them
+    pub fn is_generated(&self, file: &File) -> bool {
+        self.is_template(file) || self.is_secret(file)
+    }
+
+    /// What `import` would write for `file`, decrypted and rendered as needed
+    pub fn deployed_contents(&self, file: &File, profiles: &[String]) -> Result<Vec<u8>> {
+        let mut stored = fs::read(Dirs::Files(self).join(file.to_string()))?;
+
+        if self.is_secret(file) {
+            stored = self.decrypt(&stored)?;
+        }
+
+        if !self.is_template(file) {
+            return Ok(stored);
+        }
+
+        let template = String::from_utf8(stored)
+            .map_err(|_| Error::msg(format!("Template '{}' is not text", file.user_path())))?;
+
+        Ok(render(&template, &self.variables(profiles)?)?.into_bytes())
+    }
+
+    /// Whether the live file matches what `import` would write
+    pub fn in_sync(&self, file: &File, profiles: &[String]) -> Result<bool> {
+        let live = file.stored_path();
+        let stored = Dirs::Files(self).join(file.to_string());
+
+        // a file just marked secret still has its plaintext copy in the export
+        if self.is_secret(file) && !is_encrypted(&stored)? {
+            return Ok(false);
+        }
+
+        if self.is_generated(file) {
+            Ok(fs::read(live)? == self.deployed_contents(file, profiles)?)
+        } else {
+            Ok(same_contents(live, stored)?)
+        }
+    }
+}